@@ -1,11 +1,20 @@
 //Implements http://rosettacode.org/wiki/9_billion_names_of_God_the_integer
 
 extern crate num;
+extern crate collections;
 
 use num::bigint::BigUint;
+use collections::{Deque, RingBuf};
 use std::from_str::FromStr;
-use std::string::String;
 use std::cmp::min;
+use std::num::{Zero, One};
+use std::iter::range_inclusive;
+use std::io::IoResult;
+
+#[cfg(not(test))]
+use std::os::args;
+#[cfg(not(test))]
+use std::io::stdout;
 
 fn cumu<'a>(num: uint, cache: &'a mut Vec<Vec<BigUint>>) -> &'a Vec<BigUint> {
     let len = cache.len();
@@ -26,21 +35,239 @@ fn cumu<'a>(num: uint, cache: &'a mut Vec<Vec<BigUint>>) -> &'a Vec<BigUint> {
 }
 
 // Returns a line
-fn row(num: uint,  cache: &mut Vec<Vec<BigUint>>) -> String {
+fn row(num: uint,  cache: &mut Vec<Vec<BigUint>>) -> Vec<BigUint> {
     let r = cumu(num,cache);
-    let mut returned_string = String::new();
-    for i in range(0,num) {
-	let i = *r.get(i+1) - *r.get (i);
-	let z = i.to_str();
-	let y = z.as_slice();
-	returned_string.push_str(y);
-	returned_string.push_str(", ");
+    range(0, num).map(|i| *r.get(i+1) - *r.get(i)).collect()
+}
+
+// Returns the number of partitions P(m) of every m from 0 to n
+// using Euler's pentagonal number theorem:
+// P(m) = sum for k >= 1 of (-1)^(k+1) * (P(m - k(3k-1)/2) + P(m - k(3k+1)/2))
+// Unlike cumu, only this 1-D table is kept, so P(123456) is within reach
+pub fn partitions_table(n: uint) -> Vec<BigUint> {
+    let mut p: Vec<BigUint> = Vec::with_capacity(n + 1);
+    p.push(One::one());
+
+    for m in range(1, n + 1) {
+        // BigUint can't be negative, so we add up the terms
+        // of each sign separately
+        let mut positive: BigUint = Zero::zero();
+        let mut negative: BigUint = Zero::zero();
+
+        let mut k = 1u;
+        loop {
+            // The generalized pentagonal numbers k(3k-1)/2 and k(3k+1)/2
+            let first = k * (3 * k - 1) / 2;
+            let second = first + k;
+            if first > m {
+                break;
+            }
+
+            let mut term = p.get(m - first).clone();
+            if second <= m {
+                term = term + *p.get(m - second);
+            }
+
+            if k % 2 == 1 {
+                positive = positive + term;
+            } else {
+                negative = negative + term;
+            }
+            k += 1;
+        }
+
+        p.push(positive - negative);
+    }
+
+    p
+}
+
+// Returns P(n), the number of partitions of n, which is
+// also the sum of the n-th row of the triangle
+pub fn partitions(n: uint) -> BigUint {
+    partitions_table(n).pop().unwrap()
+}
+
+// Returns the n-th row of the triangle without caching any other
+// row. The k-th value of the row is the number of partitions of n
+// whose largest part is k, that is, the number of partitions of
+// n - k into parts no larger than k
+pub fn triangle_row(n: uint) -> Vec<BigUint> {
+    // counts.get(m) is the number of partitions of m into parts
+    // no larger than the last part size we added
+    let mut counts: Vec<BigUint> = Vec::from_fn(n + 1, |_| Zero::zero());
+    *counts.get_mut(0) = One::one();
+
+    let mut row = Vec::with_capacity(n);
+    for k in range(1, n + 1) {
+        // Allow parts of size k. Later values of the row only
+        // look at counts below n - k, so we can stop there
+        for m in range(k, n - k + 1) {
+            let sum = *counts.get(m) + *counts.get(m - k);
+            *counts.get_mut(m) = sum;
+        }
+        row.push(counts.get(n - k).clone());
+    }
+
+    row
+}
+
+// Iterator over the rows of the triangle from first to last
+// (inclusive), yielding the number of the row and its values.
+// Each row is built from the previous ones with
+// T(n, k) = T(n - 1, k - 1) + T(n - k, k)
+// (taking 1 from the largest part, or from each of the k parts).
+// T(n - k, k) is 0 once k > n / 2, so only the last half of the
+// rows is kept
+pub struct Rows {
+    // None once the last row has been returned
+    next: Option<uint>,
+    last: uint,
+    // The rows from oldest up to the last one built
+    oldest: uint,
+    previous: RingBuf<Vec<BigUint>>
+}
+
+pub fn rows(first: uint, last: uint) -> Rows {
+    Rows {
+        next: if first <= last { Some(first) } else { None },
+        last: last,
+        oldest: 0,
+        previous: RingBuf::new()
+    }
+}
+
+impl Rows {
+    // T(m, k), which must be 0 if row m is no longer kept
+    fn value(&self, m: uint, k: uint) -> BigUint {
+        if k > m || (k == 0 && m > 0) {
+            Zero::zero()
+        } else if m == 0 {
+            One::one()
+        } else {
+            self.previous.get(m - self.oldest).get(k - 1).clone()
+        }
+    }
+
+    fn build_next_row(&mut self) {
+        let m = self.oldest + self.previous.len();
+        let row: Vec<BigUint> = range_inclusive(1, m).map(|k| {
+            self.value(m - 1, k - 1) + self.value(m - k, k)
+        }).collect();
+        self.previous.push_back(row);
+
+        // The next row needs this one, and the rows from (m + 1) / 2 up
+        while self.oldest < min((m + 2) / 2, m) {
+            self.previous.pop_front();
+            self.oldest += 1;
+        }
+    }
+}
+
+impl Iterator<(uint, Vec<BigUint>)> for Rows {
+    fn next(&mut self) -> Option<(uint, Vec<BigUint>)> {
+        let n = match self.next {
+            Some(n) => n,
+            None => return None
+        };
+        while self.previous.is_empty() || self.oldest + self.previous.len() - 1 < n {
+            self.build_next_row();
+        }
+        // Checked against last rather than with n + 1, which would
+        // overflow if last is uint::MAX
+        self.next = if n == self.last { None } else { Some(n + 1) };
+        Some((n, self.previous.back().unwrap().clone()))
+    }
+}
+
+// Returns the running sums of a row. The k-th value is the number
+// of partitions of n whose largest part is at most k, and the last
+// one is P(n)
+pub fn cumulative(row: &[BigUint]) -> Vec<BigUint> {
+    let mut sum: BigUint = Zero::zero();
+    row.iter().map(|x| {
+        sum = sum + *x;
+        sum.clone()
+    }).collect()
+}
+
+#[deriving(PartialEq, Show)]
+pub enum Format {
+    Csv,
+    Json
+}
+
+// Writes the rows from first to last (inclusive) as they are
+// computed, so huge ranges can be exported.
+// The CSV has one line per value, with the columns n, k, names
+// (the value of the triangle) and cumulative (the running sum of
+// the row). The JSON is an array with one object per row
+pub fn export<W: Writer>(w: &mut W, format: Format, first: uint, last: uint) -> IoResult<()> {
+    match format {
+        Csv => try!(writeln!(w, "n,k,names,cumulative")),
+        Json => try!(w.write_str("["))
+    }
+
+    for (n, values) in rows(first, last) {
+        let sums = cumulative(values.as_slice());
+        match format {
+            Csv => {
+                for (k, (v, c)) in values.iter().zip(sums.iter()).enumerate() {
+                    try!(writeln!(w, "{},{},{},{}", n, k + 1, v, c));
+                }
+            }
+            Json => {
+                if n != first {
+                    try!(w.write_str(","));
+                }
+                try!(w.write_str("\n{\"n\": "));
+                try!(write!(w, "{}", n));
+                try!(w.write_str(", \"row\": "));
+                try!(write_json_array(w, values.as_slice()));
+                try!(w.write_str(", \"cumulative\": "));
+                try!(write_json_array(w, sums.as_slice()));
+                try!(w.write_str("}"));
+            }
+        }
+    }
+
+    match format {
+        Csv => Ok(()),
+        Json => w.write_str("\n]\n")
+    }
+}
+
+// Writes the numbers as a JSON array. JSON numbers have no size
+// limit, so we don't need to quote the big ones
+fn write_json_array<W: Writer>(w: &mut W, values: &[BigUint]) -> IoResult<()> {
+    try!(w.write_str("["));
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 {
+            try!(w.write_str(", "));
+        }
+        try!(write!(w, "{}", v));
     }
-    returned_string
+    w.write_str("]")
 }
 
 #[cfg(not(test))]
 fn main() {
+    let args = args();
+
+    // To export a range of rows:
+    //     9_billion_names_of_God_the_integer (csv|json) <first row> <last row>
+    if args.len() == 4 {
+        let format = match args.get(1).as_slice() {
+            "csv" => Csv,
+            "json" => Json,
+            other => fail!("unknown format {}, expected csv or json", other)
+        };
+        let first = from_str::<uint>(args.get(2).as_slice()).expect("invalid first row");
+        let last = from_str::<uint>(args.get(3).as_slice()).expect("invalid last row");
+        export(&mut stdout(), format, first, last).unwrap();
+        return;
+    }
+
     let mut cache: Vec<Vec<BigUint>> = Vec::new();
     let initial_value:BigUint = FromStr::from_str("1").unwrap();
     let initial_vector : Vec<BigUint> = vec!(initial_value);
@@ -54,11 +281,12 @@ fn main() {
 
     println!("sums");
 
-    let x: Vec<uint> = vec!(23, 123, 1234, 12345);
+    // The sums are the partition numbers, which we can get
+    // without building the whole triangle
+    let x: Vec<uint> = vec!(23, 123, 1234, 12345, 123456);
+    let p = partitions_table(*x.last().unwrap());
     for y in x.iter() {
-	let z = cumu(*y,&mut cache);
-	let w = z.last();
-	println!("{}: {}", y, w.unwrap());
+        println!("{}: {}", y, p.get(*y));
     }
 }
 
@@ -98,10 +326,98 @@ fn test_row() {
     let initial_vector : Vec<BigUint> = vec!(initial_value);
     cache.push(initial_vector);
 
-    let a: String = FromStr::from_str("1, 2, 1, 1, ").unwrap();
+    let a: Vec<BigUint> = vec!(One::one(), FromStr::from_str("2").unwrap(),
+                               One::one(), One::one());
 
     let x = 4;
     assert!(a == row(x,&mut cache));
 
+    let sums: Vec<BigUint> = ["1", "3", "4", "5"].iter()
+                                                 .map(|s| FromStr::from_str(*s).unwrap())
+                                                 .collect();
+    assert!(cumulative(a.as_slice()) == sums);
+}
+
+#[test]
+fn test_partitions() {
+    let expected: Vec<(uint, BigUint)> = vec!(
+        (0, FromStr::from_str("1").unwrap()),
+        (1, FromStr::from_str("1").unwrap()),
+        (5, FromStr::from_str("7").unwrap()),
+        (10, FromStr::from_str("42").unwrap()),
+        (100, FromStr::from_str("190569292").unwrap()),
+        (1000, FromStr::from_str("24061467864032622473692149727991").unwrap()));
+
+    for &(n, ref p) in expected.iter() {
+        assert!(partitions(n) == *p);
+    }
+
+    // Same values as the sums of the cached triangle
+    let mut cache: Vec<Vec<BigUint>> = vec!(vec!(One::one()));
+    let table = partitions_table(1234);
+    for &n in [23u, 123, 1234].iter() {
+        assert!(cumu(n, &mut cache).last().unwrap() == table.get(n));
+    }
+}
+
+#[test]
+fn test_triangle_row() {
+    let mut cache: Vec<Vec<BigUint>> = vec!(vec!(One::one()));
+
+    for n in range(1u, 30) {
+        let r = cumu(n, &mut cache).clone();
+        let expected: Vec<BigUint> = range(0, n).map(|i| *r.get(i + 1) - *r.get(i))
+                                                .collect();
+        assert!(triangle_row(n) == expected);
+    }
+    assert!(triangle_row(0).is_empty());
+}
+
+#[test]
+fn test_rows() {
+    for &first in [0u, 1, 2, 5, 17].iter() {
+        let mut count = 0;
+        for (n, row) in rows(first, 40) {
+            assert_eq!(n, first + count);
+            assert!(row == triangle_row(n));
+            count += 1;
+        }
+        assert_eq!(count, 41 - first);
+    }
+    assert_eq!(rows(5, 4).count(), 0);
+
+    // Only the last half of the rows is kept
+    let mut r = rows(0, 100);
+    for _ in r.by_ref() {}
+    assert_eq!(r.previous.len(), 50);
+}
+
+#[test]
+fn test_export() {
+    use std::io::MemWriter;
+    use std::str::from_utf8;
+
+    let mut csv = MemWriter::new();
+    export(&mut csv, Csv, 3, 4).unwrap();
+    assert_eq!(from_utf8(csv.get_ref()).unwrap(), "n,k,names,cumulative
+3,1,1,1
+3,2,1,2
+3,3,1,3
+4,1,1,1
+4,2,2,3
+4,3,1,4
+4,4,1,5
+");
+
+    let mut json = MemWriter::new();
+    export(&mut json, Json, 1, 2).unwrap();
+    assert_eq!(from_utf8(json.get_ref()).unwrap(), "[
+{\"n\": 1, \"row\": [1], \"cumulative\": [1]},
+{\"n\": 2, \"row\": [1, 1], \"cumulative\": [1, 2]}
+]
+");
 
+    let mut empty = MemWriter::new();
+    export(&mut empty, Json, 5, 4).unwrap();
+    assert_eq!(from_utf8(empty.get_ref()).unwrap(), "[\n]\n");
 }
@@ -1,27 +1,82 @@
 // Implements http://rosettacode.org/wiki/Almost_prime
 
-#[allow(unused_imports)]
-use std::iter::{count, range_inclusive};
+use std::iter::range_inclusive;
 
-fn is_kprime(mut n: uint, k: uint) -> bool {
-    let mut p = 2;
-    let mut f = 0;
+// Omega(n), the number of prime factors of n counted with multiplicity, and
+// omega(n), the number of distinct prime factors, for every n up to a limit
+pub struct FactorCounts {
+    big_omega: Vec<u8>,
+    small_omega: Vec<u8>
+}
 
-    while f < k && p * p <= n {
-        while 0 == n % p {
-            n /= p;
-            f += 1;
+impl FactorCounts {
+    // A single pass of the linear sieve: every composite m is reached once,
+    // as p * n where p is its smallest prime factor, after the counts of n
+    // are known. See http://en.wikipedia.org/wiki/Sieve_of_Eratosthenes
+    pub fn new(limit: uint) -> FactorCounts {
+        let mut smallest = Vec::from_elem(limit + 1, 0u);
+        let mut primes = vec![];
+        let mut big_omega = Vec::from_elem(limit + 1, 0u8);
+        let mut small_omega = Vec::from_elem(limit + 1, 0u8);
+        for n in range_inclusive(2, limit) {
+            if *smallest.get(n) == 0 {
+                // n is a prime
+                *smallest.get_mut(n) = n;
+                *big_omega.get_mut(n) = 1;
+                *small_omega.get_mut(n) = 1;
+                primes.push(n);
+            }
+
+            let smallest_of_n = *smallest.get(n);
+            for &p in primes.iter() {
+                if p > smallest_of_n || p * n > limit {
+                    break;
+                }
+                let m = p * n;
+                *smallest.get_mut(m) = p;
+                *big_omega.get_mut(m) = *big_omega.get(n) + 1;
+                // p is a new factor unless it also divides n
+                *small_omega.get_mut(m) = *small_omega.get(n) +
+                                          if p == smallest_of_n { 0 } else { 1 };
+            }
         }
-        p += 1;
+
+        FactorCounts { big_omega: big_omega, small_omega: small_omega }
     }
 
-    f + (n > 1) as uint == k
+    // The largest number in the table
+    pub fn limit(&self) -> uint {
+        self.big_omega.len() - 1
+    }
+
+    // Number of prime factors of n, counted with multiplicity
+    pub fn big_omega(&self, n: uint) -> uint {
+        *self.big_omega.get(n) as uint
+    }
+
+    // Number of distinct prime factors of n
+    pub fn small_omega(&self, n: uint) -> uint {
+        *self.small_omega.get(n) as uint
+    }
+
+    // The k-almost-primes up to the limit, in increasing order
+    pub fn kprimes(&self, k: uint) -> Vec<uint> {
+        range_inclusive(2, self.limit()).filter(|&n| self.big_omega(n) == k)
+                                        .collect()
+    }
 }
 
+// Returns the first amount k-almost-primes. We don't know in advance how far
+// they go, so we double the size of the sieve until we have enough of them
 fn get_kprimes(k: uint, amount: uint) -> Vec<uint> {
-    count(2u, 1).filter(|&x| is_kprime(x, k))
-                .take(amount)
-                .collect()
+    let mut limit = 64;
+    loop {
+        let kprimes = FactorCounts::new(limit).kprimes(k);
+        if kprimes.len() >= amount {
+            return kprimes.move_iter().take(amount).collect();
+        }
+        limit *= 2;
+    }
 }
 
 #[cfg(not(test))]
@@ -31,6 +86,23 @@ fn main() {
     }
 }
 
+// Trial division, to check the sieve
+#[cfg(test)]
+fn is_kprime(mut n: uint, k: uint) -> bool {
+    let mut p = 2;
+    let mut f = 0;
+
+    while f < k && p * p <= n {
+        while 0 == n % p {
+            n /= p;
+            f += 1;
+        }
+        p += 1;
+    }
+
+    f + (n > 1) as uint == k
+}
+
 #[test]
 fn test_almost_primes() {
     // k = 1
@@ -48,3 +120,31 @@ fn test_almost_primes() {
     // k = 5
     assert!(get_kprimes(5, 10).as_slice() == [32, 48, 72, 80, 108, 112, 120, 162, 168, 176]);
 }
+
+#[test]
+fn test_omega() {
+    let counts = FactorCounts::new(1000);
+    assert_eq!(counts.limit(), 1000);
+
+    // 360 = 2^3 * 3^2 * 5
+    assert_eq!(counts.big_omega(360), 6);
+    assert_eq!(counts.small_omega(360), 3);
+    // 1 has no prime factors, 997 is a prime
+    assert_eq!(counts.big_omega(1), 0);
+    assert_eq!(counts.small_omega(1), 0);
+    assert_eq!(counts.big_omega(997), 1);
+    assert_eq!(counts.small_omega(997), 1);
+    // 512 = 2^9
+    assert_eq!(counts.big_omega(512), 9);
+    assert_eq!(counts.small_omega(512), 1);
+}
+
+#[test]
+fn test_against_trial_division() {
+    let counts = FactorCounts::new(5000);
+    for n in range_inclusive(2u, 5000) {
+        for k in range_inclusive(1u, 12) {
+            assert_eq!(counts.big_omega(n) == k, is_kprime(n, k));
+        }
+    }
+}
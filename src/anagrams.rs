@@ -4,12 +4,15 @@ extern crate collections;
 
 use collections::{HashMap, HashSet};
 use std::str;
+use std::io::{IoError, IoResult, InvalidInput};
 
 #[cfg(test)]
-use std::io::MemReader;
+use std::io::{MemReader, MemWriter};
 
 #[cfg(not(test))]
-use std::io::{File, BufferedReader};
+use std::io::{File, BufferedReader, BufferedWriter};
+#[cfg(not(test))]
+use std::os::args;
 use std::cmp::max;
 
 fn sort_string(string: &str) -> String {
@@ -55,20 +58,244 @@ fn get_biggest_groups(groups: &HashMap<String, HashSet<String>>)
         .collect()
 }
 
+// The first line of an index file, so we don't load anything else by mistake
+static INDEX_HEADER: &'static str = "anagram index 1";
+
+// The groups of anagrams of a word list, which can be saved to a file and
+// loaded again without going through the whole list. The file has a line
+// per group, with the sorted letters and then the words, separated by tabs
+// so that words can have spaces in them:
+//     aegln<TAB>angel<TAB>angle<TAB>galen
+pub struct AnagramIndex {
+    // The words of every group, in order, by their sorted letters
+    groups: HashMap<String, Vec<String>>
+}
+
+impl AnagramIndex {
+    // Builds the index of a word list with a word per line
+    pub fn from_words<T: Buffer>(mut reader: T) -> IoResult<AnagramIndex> {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for line in reader.lines() {
+            let line = try!(line);
+            let word = line.as_slice().trim();
+            // Blank lines, and words we couldn't tell apart in the file
+            if word.is_empty() || word.contains_char('\t') {
+                continue;
+            }
+            groups.insert_or_update_with(sort_string(word), vec![word.to_string()],
+                                         |_, words| words.push(word.to_string()));
+        }
+        for (_, words) in groups.mut_iter() {
+            words.sort();
+            words.dedup();
+        }
+        Ok(AnagramIndex { groups: groups })
+    }
+
+    pub fn save<W: Writer>(&self, w: &mut W) -> IoResult<()> {
+        try!(w.write_line(INDEX_HEADER));
+
+        // Sorted, so the same list always gives the same file
+        let mut keys: Vec<String> = self.groups.keys().map(|key| key.clone()).collect();
+        keys.sort();
+        for key in keys.iter() {
+            try!(w.write_str(key.as_slice()));
+            for word in self.groups.get(key).iter() {
+                try!(w.write_char('\t'));
+                try!(w.write_str(word.as_slice()));
+            }
+            try!(w.write_char('\n'));
+        }
+        Ok(())
+    }
+
+    // Loads an index written by save. The groups are taken as they are,
+    // without sorting the letters of every word again
+    pub fn load<T: Buffer>(mut reader: T) -> IoResult<AnagramIndex> {
+        let header = try!(reader.read_line());
+        if header.as_slice().trim() != INDEX_HEADER {
+            return Err(invalid_index("missing header"));
+        }
+
+        let mut groups = HashMap::new();
+        for line in reader.lines() {
+            let line = try!(line);
+            // Only the end of line goes, spaces are part of the words
+            let line = line.as_slice().trim_right_chars('\n');
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let key = fields.next().unwrap().to_string();
+            let words: Vec<String> = fields.map(|word| word.to_string()).collect();
+            if words.is_empty() || words.iter().any(|word| word.is_empty()) {
+                return Err(invalid_index("group without words"));
+            }
+            groups.insert(key, words);
+        }
+        Ok(AnagramIndex { groups: groups })
+    }
+
+    // The words made of the same letters as word. It is one of them if it
+    // is in the list
+    pub fn anagrams_of<'a>(&'a self, word: &str) -> &'a [String] {
+        match self.groups.find(&sort_string(word)) {
+            Some(words) => words.as_slice(),
+            None => &[]
+        }
+    }
+
+    // The count groups with the most words, biggest first
+    pub fn largest_groups<'a>(&'a self, count: uint) -> Vec<&'a [String]> {
+        let mut groups: Vec<&'a [String]> = self.groups.values()
+                                                       .map(|group| group.as_slice())
+                                                       .collect();
+        groups.sort_by(|a, b| match b.len().cmp(&a.len()) {
+            Equal => a[0].cmp(&b[0]),
+            order => order
+        });
+        groups.truncate(count);
+        groups
+    }
+
+    // The words which can be spelled with the given letters, using each
+    // letter at most as many times as it is given. Longest words first
+    pub fn words_using(&self, letters: &str) -> Vec<String> {
+        let available: Vec<char> = sort_string(letters).as_slice().chars().collect();
+        let mut words = vec![];
+        for (key, group) in self.groups.iter() {
+            if uses_only(key.as_slice(), available.as_slice()) {
+                words.push_all(group.as_slice());
+            }
+        }
+        words.sort_by(|a, b| match b.len().cmp(&a.len()) {
+            Equal => a.cmp(b),
+            order => order
+        });
+        words
+    }
+}
+
+fn invalid_index(detail: &str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "invalid anagram index",
+        detail: Some(detail.to_string())
+    }
+}
+
+// Whether the sorted letters needed are among the sorted letters available
+fn uses_only(needed: &str, available: &[char]) -> bool {
+    let mut available = available.iter();
+    'letters: for c in needed.chars() {
+        for &a in available.by_ref() {
+            if a == c {
+                continue 'letters;
+            }
+            if a > c {
+                return false;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+#[cfg(not(test))]
+fn load_index(path: &Path) -> AnagramIndex {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => fail!("couldn't open {}: {}", path.display(), e)
+    };
+    match AnagramIndex::load(BufferedReader::new(file)) {
+        Ok(index) => index,
+        Err(e) => fail!("couldn't load {}: {}", path.display(), e)
+    }
+}
+
+#[cfg(not(test))]
+fn print_words(words: &[String]) {
+    println!("{}", words.iter().map(|word| word.as_slice()).collect::<Vec<&str>>().connect(" "));
+}
+
 #[cfg(not(test))]
 fn main () {
-    let path = Path::new("src/resources/unixdict.txt");
-    let reader = BufferedReader::new(File::open(&path));
+    let args = args();
+
+    // Without arguments we just solve the Rosetta task. Otherwise:
+    //     anagrams build <word list> <index>
+    //     anagrams of <index> <word>
+    //     anagrams largest <index> [number of groups]
+    //     anagrams using <index> <letters>
+    if args.len() < 2 {
+        let path = Path::new("src/resources/unixdict.txt");
+        let reader = BufferedReader::new(File::open(&path));
+
+        let anagram_groups = get_anagrams(reader);
+        let biggest_groups = get_biggest_groups(&anagram_groups);
+
+        // Print the words in the biggest groups of anagrams
+        for (_, group) in biggest_groups.iter() {
+            for word in group.iter() {
+                print!("{} ", *word)
+            }
+            println!("")
+        }
+        return;
+    }
 
-    let anagram_groups = get_anagrams(reader);
-    let biggest_groups = get_biggest_groups(&anagram_groups);
+    if args.len() < 3 {
+        fail!("usage: {} (build <word list>|of|largest|using) <index> ...", args.get(0));
+    }
+    match args.get(1).as_slice() {
+        "build" => {
+            if args.len() < 4 {
+                fail!("usage: {} build <word list> <index>", args.get(0));
+            }
+            let words = Path::new(args.get(2).as_slice());
+            let file = match File::open(&words) {
+                Ok(f) => f,
+                Err(e) => fail!("couldn't open {}: {}", words.display(), e)
+            };
+            let index = match AnagramIndex::from_words(BufferedReader::new(file)) {
+                Ok(index) => index,
+                Err(e) => fail!("couldn't read {}: {}", words.display(), e)
+            };
 
-    // Print the words in the biggest groups of anagrams
-    for (_, group) in biggest_groups.iter() {
-        for word in group.iter() {
-            print!("{} ", *word)
+            let path = Path::new(args.get(3).as_slice());
+            let mut writer = match File::create(&path) {
+                Ok(f) => BufferedWriter::new(f),
+                Err(e) => fail!("couldn't create {}: {}", path.display(), e)
+            };
+            match index.save(&mut writer).and_then(|_| writer.flush()) {
+                Ok(_) => println!("{} groups of anagrams", index.groups.len()),
+                Err(e) => fail!("couldn't write {}: {}", path.display(), e)
+            }
+        }
+        "of" => {
+            let index = load_index(&Path::new(args.get(2).as_slice()));
+            for word in args.slice_from(3).iter() {
+                print_words(index.anagrams_of(word.as_slice()));
+            }
+        }
+        "largest" => {
+            let index = load_index(&Path::new(args.get(2).as_slice()));
+            let count = match args.len() {
+                3 => 10,
+                _ => from_str::<uint>(args.get(3).as_slice())
+                         .expect("the number of groups must be a positive integer")
+            };
+            for group in index.largest_groups(count).iter() {
+                print_words(*group);
+            }
+        }
+        "using" => {
+            let index = load_index(&Path::new(args.get(2).as_slice()));
+            for letters in args.slice_from(3).iter() {
+                print_words(index.words_using(letters.as_slice()).as_slice());
+            }
         }
-        println!("")
+        other => fail!("unknown command {}", other)
     }
 }
 
@@ -113,3 +340,90 @@ fn basic_test() {
     assert!(biggest_groups.iter().any(|(_, group)| *group == group2));
     assert!(biggest_groups.iter().all(|(_, group)| *group != group3));
 }
+
+#[cfg(test)]
+fn test_index() -> AnagramIndex {
+    let words = "lane\nneal\nlean\nangel\nangle\ngalen\nglare\nlarge\nan\na\n\nnan\n";
+    AnagramIndex::from_words(MemReader::new(words.bytes().collect())).unwrap()
+}
+
+#[cfg(test)]
+fn to_strs<'a>(words: &'a [String]) -> Vec<&'a str> {
+    words.iter().map(|word| word.as_slice()).collect()
+}
+
+#[test]
+fn test_anagrams_of() {
+    let index = test_index();
+    assert_eq!(to_strs(index.anagrams_of("elan")), vec!["lane", "lean", "neal"]);
+    assert_eq!(to_strs(index.anagrams_of("large")), vec!["glare", "large"]);
+    assert!(index.anagrams_of("rust").is_empty());
+    // The blank line isn't a word
+    assert!(index.anagrams_of("").is_empty());
+}
+
+#[test]
+fn test_largest_groups() {
+    let index = test_index();
+    let groups: Vec<Vec<&str>> = index.largest_groups(3).iter().map(|g| to_strs(*g)).collect();
+    assert_eq!(groups, vec![vec!["angel", "angle", "galen"],
+                            vec!["lane", "lean", "neal"],
+                            vec!["glare", "large"]]);
+    assert_eq!(index.largest_groups(100).len(), 6);
+}
+
+#[test]
+fn test_words_using() {
+    let index = test_index();
+    assert_eq!(to_strs(index.words_using("enlaq").as_slice()),
+               vec!["lane", "lean", "neal", "an", "a"]);
+    // There is only one n
+    assert_eq!(to_strs(index.words_using("an").as_slice()), vec!["an", "a"]);
+    assert_eq!(to_strs(index.words_using("nan").as_slice()), vec!["nan", "an", "a"]);
+    assert!(index.words_using("xyz").is_empty());
+}
+
+#[test]
+fn test_save_load() {
+    let index = test_index();
+    let mut writer = MemWriter::new();
+    index.save(&mut writer).unwrap();
+    let saved = writer.unwrap();
+    assert!(str::from_utf8(saved.as_slice()).unwrap()
+                .starts_with("anagram index 1\na\ta\naegln\tangel\tangle\tgalen\n"));
+
+    let loaded = AnagramIndex::load(MemReader::new(saved)).unwrap();
+    assert_eq!(loaded.groups, index.groups);
+
+    // No header, and a group without words
+    for bad in ["aegln\tangel\n", "anagram index 1\naegln\n", "anagram index 1\naegln\t\n"].iter() {
+        assert!(AnagramIndex::load(MemReader::new(bad.bytes().collect())).is_err());
+    }
+}
+
+#[test]
+fn test_words_with_spaces() {
+    let words = "ice cream\nmace rice\nlean\nt\tab\n";
+    let index = AnagramIndex::from_words(MemReader::new(words.bytes().collect())).unwrap();
+    assert_eq!(to_strs(index.anagrams_of("cream ice")), vec!["ice cream", "mace rice"]);
+    // A tab would split the word in the file
+    assert!(index.anagrams_of("t\tab").is_empty());
+
+    let mut writer = MemWriter::new();
+    index.save(&mut writer).unwrap();
+    let loaded = AnagramIndex::load(MemReader::new(writer.unwrap())).unwrap();
+    assert_eq!(loaded.groups, index.groups);
+    assert_eq!(to_strs(loaded.anagrams_of("ice cream")), vec!["ice cream", "mace rice"]);
+}
+
+#[test]
+fn test_read_error() {
+    // Not UTF-8, so the line can't be read
+    let words = vec![0xffu8, 0xfe, 10];
+    assert!(AnagramIndex::from_words(MemReader::new(words)).is_err());
+
+    // The same word twice is only listed once
+    let words = "lean\nlane\nlean\n";
+    let index = AnagramIndex::from_words(MemReader::new(words.bytes().collect())).unwrap();
+    assert_eq!(to_strs(index.anagrams_of("lean")), vec!["lane", "lean"]);
+}
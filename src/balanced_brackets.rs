@@ -1,28 +1,300 @@
 // Implements http://rosettacode.org/wiki/Balanced_brackets
 
+use std::cmp::min;
+use std::num::CheckedAdd;
+use std::rand::Rng;
 #[cfg(not(test))]
-use std::rand::random;
+use std::rand::{random, task_rng};
+
+// Why a string isn't balanced. Positions are indices of characters, not
+// of bytes
+#[deriving(Show, PartialEq)]
+pub enum BracketError {
+    // A closer at some position, with no opener left to close
+    UnexpectedCloser(uint, char),
+    // The opener at the first position is closed by a closer of another
+    // pair at the second position
+    MismatchedPair(uint, char, uint, char),
+    // An opener at some position which is never closed
+    Unclosed(uint, char),
+    // A character which isn't a bracket, when we don't skip them
+    UnexpectedCharacter(uint, char),
+    // A quote at some position which is never closed
+    UnclosedQuote(uint, char)
+}
+
+// Checks the brackets of a string, for a set of bracket pairs
+pub struct BracketChecker {
+    pairs: Vec<(char, char)>,
+    skip_others: bool,
+    quotes: Vec<char>
+}
+
+impl BracketChecker {
+    // The pairs are given as a string of openers followed by their closers,
+    // like "()[]{}". By default any other character is an error, and there
+    // are no quotes
+    pub fn new(pairs: &str) -> BracketChecker {
+        let chars: Vec<char> = pairs.chars().collect();
+        assert!(chars.len() % 2 == 0, "every opener needs a closer");
+        BracketChecker {
+            pairs: chars.as_slice().chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+            skip_others: false,
+            quotes: vec![]
+        }
+    }
+
+    // Whether the characters which aren't brackets or quotes are ignored
+    pub fn skip_others(self, skip_others: bool) -> BracketChecker {
+        BracketChecker { skip_others: skip_others, ..self }
+    }
+
+    // Characters which start and end quoted text, where brackets don't
+    // count. A backslash escapes the next character inside quotes
+    pub fn quotes(self, quotes: &str) -> BracketChecker {
+        BracketChecker { quotes: quotes.chars().collect(), ..self }
+    }
+
+    fn closer_of(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|&&(open, _)| open == c).map(|&(_, close)| close)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|&(_, close)| close == c)
+    }
+
+    pub fn check(&self, s: &str) -> Result<(), BracketError> {
+        // The openers which are still open, with their positions
+        let mut open: Vec<(uint, char)> = vec![];
+        // The quote we are in, with its position
+        let mut quote: Option<(uint, char)> = None;
+        let mut escaped = false;
+
+        for (i, c) in s.chars().enumerate() {
+            match quote {
+                Some((_, q)) => {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == q {
+                        quote = None;
+                    }
+                    continue;
+                }
+                None => {}
+            }
+
+            if self.quotes.contains(&c) {
+                quote = Some((i, c));
+            } else if self.closer_of(c).is_some() {
+                open.push((i, c));
+            } else if self.is_closer(c) {
+                match open.pop() {
+                    None => return Err(UnexpectedCloser(i, c)),
+                    Some((j, opener)) => {
+                        if self.closer_of(opener) != Some(c) {
+                            return Err(MismatchedPair(j, opener, i, c));
+                        }
+                    }
+                }
+            } else if !self.skip_others {
+                return Err(UnexpectedCharacter(i, c));
+            }
+        }
+
+        match quote {
+            Some((i, q)) => return Err(UnclosedQuote(i, q)),
+            None => {}
+        }
+        match open.pop() {
+            // The innermost one, which should have been closed first
+            Some((i, opener)) => Err(Unclosed(i, opener)),
+            None => Ok(())
+        }
+    }
+}
 
 // Returns true if the brackets are balanced
 fn check_balanced(bracket_str: &str) -> bool {
-    let mut count = 0;
+    BracketChecker::new("[]").check(bracket_str).is_ok()
+}
+
+// The balanced strings of n pairs of square brackets. They are ordered
+// lexicographically, and '[' comes before ']'
+pub struct BalancedStrings {
+    pairs: uint,
+    // completions.get(r).get(h) is the number of ways to finish a string
+    // with r characters left, when h brackets are open. Only the cases we
+    // can get to are filled in. None of those is bigger than the number
+    // of balanced strings, while some of the others wouldn't fit in a u64
+    completions: Vec<Vec<u64>>
+}
 
-    for bracket in bracket_str.chars() {
-        match bracket {
-            '[' => {
-                count += 1;
+impl BalancedStrings {
+    // The counts are u64, which is enough for up to 36 pairs. It fails for
+    // more, because C(37) doesn't fit
+    pub fn new(pairs: uint) -> BalancedStrings {
+        let len = 2 * pairs;
+        let mut completions = Vec::from_elem(len + 1, Vec::from_elem(len + 2, 0u64));
+        *completions.get_mut(0).get_mut(0) = 1;
+        for r in range(1, len + 1) {
+            for h in range(0, min(r, len - r) + 1) {
+                let open = *completions.get(r - 1).get(h + 1);
+                let close = if h > 0 { *completions.get(r - 1).get(h - 1) } else { 0 };
+                *completions.get_mut(r).get_mut(h) = open.checked_add(&close)
+                                                         .expect("too many pairs");
             }
-            ']' => {
-                count -= 1;
-                if count < 0 {
-                    return false;
+        }
+        BalancedStrings { pairs: pairs, completions: completions }
+    }
+
+    fn completions(&self, left: uint, open: uint) -> u64 {
+        if open > left { 0 } else { *self.completions.get(left).get(open) }
+    }
+
+    // The number of balanced strings, which is the Catalan number C(n)
+    pub fn count(&self) -> u64 {
+        self.completions(2 * self.pairs, 0)
+    }
+
+    // The position of s among the balanced strings, or None if it isn't one
+    // of them. Every ']' skips the strings which have '[' there instead
+    pub fn rank(&self, s: &str) -> Option<u64> {
+        let len = 2 * self.pairs;
+        if s.char_len() != len {
+            return None;
+        }
+
+        let mut rank = 0;
+        let mut open = 0u;
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '[' => open += 1,
+                ']' if open > 0 => {
+                    rank += self.completions(len - i - 1, open + 1);
+                    open -= 1;
                 }
+                _ => return None
             }
-            _ => { fail!("Strings containing characters other than brackets are not allowed"); }
         }
+        if open == 0 { Some(rank) } else { None }
     }
 
-    count == 0
+    // The balanced string at some position, or None if there are fewer
+    // strings than that
+    pub fn unrank(&self, rank: u64) -> Option<String> {
+        if rank >= self.count() {
+            return None;
+        }
+
+        let len = 2 * self.pairs;
+        let mut rank = rank;
+        let mut open = 0u;
+        let mut s = String::with_capacity(len);
+        for i in range(0, len) {
+            let with_opener = self.completions(len - i - 1, open + 1);
+            if rank < with_opener {
+                s.push_char('[');
+                open += 1;
+            } else {
+                rank -= with_opener;
+                s.push_char(']');
+                open -= 1;
+            }
+        }
+        Some(s)
+    }
+
+    // All the balanced strings, in order
+    pub fn iter(&self) -> Lexicographic {
+        Lexicographic { pairs: self.pairs, current: None, done: false }
+    }
+}
+
+// Iterator over the balanced strings of some number of pairs in
+// lexicographic order, going from each string to the next one
+pub struct Lexicographic {
+    pairs: uint,
+    // true for '[' and false for ']'
+    current: Option<Vec<bool>>,
+    done: bool
+}
+
+impl Iterator<String> for Lexicographic {
+    fn next(&mut self) -> Option<String> {
+        if self.done {
+            return None;
+        }
+
+        let next = match self.current {
+            // The first one has all the openers first
+            None => Some(Vec::from_fn(2 * self.pairs, |i| i < self.pairs)),
+            Some(ref s) => successor(s.as_slice())
+        };
+        match next {
+            Some(s) => {
+                let string = s.iter().map(|&open| if open { '[' } else { ']' }).collect();
+                self.current = Some(s);
+                Some(string)
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+// The next balanced string: we change the last '[' which can become ']',
+// and put the remaining openers as early as possible after it
+fn successor(s: &[bool]) -> Option<Vec<bool>> {
+    // The number of open brackets before each position
+    let mut heights = Vec::with_capacity(s.len());
+    let mut height = 0u;
+    for &open in s.iter() {
+        heights.push(height);
+        height = if open { height + 1 } else { height - 1 };
+    }
+
+    for i in range(0, s.len()).rev() {
+        if s[i] && *heights.get(i) > 0 {
+            let openers_before = (i + *heights.get(i)) / 2;
+            let openers_left = s.len() / 2 - openers_before;
+            let mut next = s.slice_to(i).to_vec();
+            next.push(false);
+            next.grow(openers_left, &true);
+            let len = s.len();
+            next.grow(len - i - 1 - openers_left, &false);
+            return Some(next);
+        }
+    }
+    None
+}
+
+// Generates a uniformly random balanced string of n pairs, with the cycle
+// lemma: of the 2n + 1 rotations of a sequence of n openers and n + 1
+// closers, exactly one is a balanced string followed by a closer. It is
+// the one starting after the first place where the count of open brackets
+// reaches its minimum
+pub fn random_balanced<R: Rng>(rng: &mut R, pairs: uint) -> String {
+    let mut brackets = Vec::from_fn(2 * pairs + 1, |i| i < pairs);
+    rng.shuffle(brackets.as_mut_slice());
+
+    let mut height = 0i;
+    let mut lowest = 0i;
+    let mut start = 0u;
+    for (i, &open) in brackets.iter().enumerate() {
+        height += if open { 1 } else { -1 };
+        if height < lowest {
+            lowest = height;
+            start = i + 1;
+        }
+    }
+
+    let len = brackets.len();
+    range(0, len - 1).map(|i| if *brackets.get((start + i) % len) { '[' } else { ']' })
+                     .collect()
 }
 
 // Generates random brackets
@@ -50,6 +322,26 @@ fn main() {
 
         println!("{:s}    {:b}", brackets, balanced)
     }
+
+    // Balanced strings, chosen uniformly
+    let mut rng = task_rng();
+    for n in range(0u, 5) {
+        println!("{}", random_balanced(&mut rng, n));
+    }
+
+    let three = BalancedStrings::new(3);
+    for (rank, s) in three.iter().enumerate() {
+        println!("{}: {}", rank, s);
+    }
+    let twenty = BalancedStrings::new(20);
+    println!("There are {} balanced strings of 20 pairs, the millionth one is {}",
+             twenty.count(), twenty.unrank(999999).unwrap());
+
+    // Every kind of bracket, in a line of some config file
+    let checker = BracketChecker::new("()[]{}<>").skip_others(true).quotes("\"'");
+    for line in ["items = [1, (2, 3), {a: \"]\"}]", "items = [1, (2, 3]", "items = <a"].iter() {
+        println!("{}    {}", line, checker.check(*line));
+    }
 }
 
 #[test]
@@ -70,3 +362,105 @@ fn test_good_brackets() {
     assert!(check_balanced("[][]"));
     assert!(check_balanced("[[][]]"));
 }
+
+#[test]
+fn test_other_characters() {
+    assert!(!check_balanced("[a]"));
+    assert_eq!(BracketChecker::new("[]").check("[a]"), Err(UnexpectedCharacter(1, 'a')));
+    assert_eq!(BracketChecker::new("[]").skip_others(true).check("[a]"), Ok(()));
+}
+
+#[test]
+fn test_errors() {
+    let checker = BracketChecker::new("()[]{}<>").skip_others(true);
+    assert_eq!(checker.check("f(x) = {[<a>]}"), Ok(()));
+    assert_eq!(checker.check("a)"), Err(UnexpectedCloser(1, ')')));
+    assert_eq!(checker.check("([)]"), Err(MismatchedPair(1, '[', 2, ')')));
+    assert_eq!(checker.check("{ (x) [y"), Err(Unclosed(6, '[')));
+    assert_eq!(checker.check("{ (x) y"), Err(Unclosed(0, '{')));
+    // Positions count characters, not bytes
+    assert_eq!(checker.check("é)"), Err(UnexpectedCloser(1, ')')));
+}
+
+#[test]
+fn test_quotes() {
+    let checker = BracketChecker::new("()[]{}").skip_others(true).quotes("\"'");
+    assert_eq!(checker.check("[\"]\", ')']"), Ok(()));
+    assert_eq!(checker.check("(\"a\\\"(\")"), Ok(()));
+    assert_eq!(checker.check("('it)"), Err(UnclosedQuote(1, '\'')));
+    // Without quotes they are just other characters
+    let no_quotes = BracketChecker::new("()[]{}").skip_others(true);
+    assert_eq!(no_quotes.check("[\"]\"]"), Err(UnexpectedCloser(4, ']')));
+}
+
+#[test]
+fn test_count() {
+    // http://oeis.org/A000108
+    let catalan = [1u64, 1, 2, 5, 14, 42, 132, 429, 1430, 4862, 16796];
+    for (n, &c) in catalan.iter().enumerate() {
+        assert_eq!(BalancedStrings::new(n).count(), c);
+        assert_eq!(BalancedStrings::new(n).iter().count() as u64, c);
+    }
+    assert_eq!(BalancedStrings::new(35).count(), 3116285494907301262);
+    assert_eq!(BalancedStrings::new(36).count(), 11959798385860453492);
+}
+
+#[test]
+#[should_fail]
+fn test_too_many_pairs() {
+    BalancedStrings::new(37);
+}
+
+#[test]
+fn test_lexicographic() {
+    let three: Vec<String> = BalancedStrings::new(3).iter().collect();
+    let expected: Vec<String> = ["[[[]]]", "[[][]]", "[[]][]", "[][[]]", "[][][]"]
+                                    .iter().map(|s| s.to_string()).collect();
+    assert_eq!(three, expected);
+
+    for n in range(0u, 9) {
+        let all: Vec<String> = BalancedStrings::new(n).iter().collect();
+        for pair in all.as_slice().windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+        assert!(all.iter().all(|s| check_balanced(s.as_slice())));
+    }
+}
+
+#[test]
+fn test_rank() {
+    for n in range(0u, 9) {
+        let strings = BalancedStrings::new(n);
+        for (i, s) in strings.iter().enumerate() {
+            assert_eq!(strings.rank(s.as_slice()), Some(i as u64));
+            assert_eq!(strings.unrank(i as u64), Some(s));
+        }
+        assert_eq!(strings.unrank(strings.count()), None);
+    }
+
+    let three = BalancedStrings::new(3);
+    assert_eq!(three.rank("[]]["), None);
+    assert_eq!(three.rank("[[]]"), None);
+    assert_eq!(three.rank("[[]]]["), None);
+    assert_eq!(three.rank("[(])[]"), None);
+    assert_eq!(three.rank("[[[[]]"), None);
+}
+
+#[test]
+fn test_random_balanced() {
+    let mut rng = std::rand::task_rng();
+    for n in range(0u, 20) {
+        let s = random_balanced(&mut rng, n);
+        assert_eq!(s.len(), 2 * n);
+        assert!(check_balanced(s.as_slice()));
+    }
+
+    // Every string of 3 pairs should come up about a fifth of the time
+    let three = BalancedStrings::new(3);
+    let mut seen = [0u, ..5];
+    for _ in range(0u, 10000) {
+        let s = random_balanced(&mut rng, 3);
+        seen[three.rank(s.as_slice()).unwrap() as uint] += 1;
+    }
+    assert!(seen.iter().all(|&count| 1800 < count && count < 2200));
+}
@@ -1,14 +1,66 @@
 //Implements http://rosettacode.org/wiki/Sorting_algorithms/Bubble_sort
+#[cfg(test)]
+use std::cell::RefCell;
+
+// The sort only looks at the slice through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    fn greater(&mut self, v: &[T], i: uint, j: uint) -> bool;
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        v.swap(i, j);
+    }
+}
+
+struct Plain;
+
+impl<T: PartialOrd> Steps<T> for Plain {
+    fn greater(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        v[i] > v[j]
+    }
+}
+
+// Tells the caller of sort_watched about every comparison and swap
+struct Watched<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering,
+    compared: |uint, uint|: 'a,
+    swapped: |uint, uint|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn greater(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        (self.compared)(i, j);
+        (self.compare)(&v[i], &v[j]) == Greater
+    }
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        (self.swapped)(i, j);
+        v.swap(i, j);
+    }
+}
+
 fn bubble_sort<T: PartialOrd>(v: &mut[T]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts v in the order given by compare, and calls compared and swapped
+// with the positions of every comparison and swap
+pub fn sort_watched<T>(v: &mut [T], compare: |&T, &T| -> Ordering,
+                       compared: |uint, uint|, swapped: |uint, uint|) {
+    let mut watched = Watched { compare: compare, compared: compared, swapped: swapped };
+    sort_steps(v, &mut watched);
+}
+
+fn sort_steps<T, S: Steps<T>>(v: &mut [T], steps: &mut S) {
     let mut done = v.len()<1;
     let mut length = v.len();
     while !done {
         done = true;
         length = length-1;
         for index in range(0, length) {
-            if v[index] > v[index+1] {
+            if steps.greater(v, index, index+1) {
                 done = false;
-                v.swap(index, index+1);
+                steps.swap(v, index, index+1);
             }
         }
     }
@@ -18,55 +70,31 @@ fn bubble_sort<T: PartialOrd>(v: &mut[T]) {
 fn main() {
     let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
     bubble_sort(numbers);
-}
-
-#[cfg(test)]
-fn check_sort<T: PartialOrd>(v: &[T]) {
-    if v.len() > 1 {
-        for i in range(0, v.len()-1) {
-            assert!(v[i] <= v[i+1]);
-        }
-    }
-}
-
-#[test]
-fn test_rosetta_vector() {
-    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
-    bubble_sort(numbers);
-    check_sort(numbers);
-}
-
-#[test]
-fn test_empty_vector() {
-    let mut numbers: ~[int] = ~[];
-    bubble_sort(numbers);
-    check_sort(numbers);
+    println!("{}", numbers.as_slice());
 }
 
 #[test]
-fn test_one_element_vector() {
-    let mut numbers = [0];
+fn test_bubble_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
     bubble_sort(numbers);
-    check_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
 }
 
 #[test]
-fn test_repeat_vector() {
-    let mut numbers = [1, 1, 1, 1, 1];
+fn test_partial_ord() {
+    let mut numbers = [0.5f64, -1.0, 3.25, 0.0];
     bubble_sort(numbers);
-    check_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-1.0, 0.0, 0.5, 3.25].as_slice());
 }
 
 #[test]
-fn test_worst_case_vector() {
-    let mut numbers = [20, 10, 0, -1, -5];
-    bubble_sort(numbers);
-    check_sort(numbers);
-}
-
-#[test]
-fn test_already_sorted_vector() {
-    let mut numbers = [-1, 0, 3, 6, 99];
-    bubble_sort(numbers);
-    check_sort(numbers);
+fn test_sort_watched() {
+    let steps = RefCell::new(vec![]);
+    let mut numbers = [3i, 1, 2];
+    sort_watched(numbers, |a, b| a.cmp(b),
+                 |i, j| steps.borrow_mut().push(("compare", i, j)),
+                 |i, j| steps.borrow_mut().push(("swap", i, j)));
+    assert_eq!(numbers.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!(*steps.borrow(), vec![("compare", 0, 1), ("swap", 0, 1), ("compare", 1, 2),
+                                     ("swap", 1, 2), ("compare", 0, 1)]);
 }
@@ -0,0 +1,200 @@
+// Implements http://rosettacode.org/wiki/Compare_sorting_algorithms'_performance
+// by counting what each sort does, instead of timing it
+use sorting::{Sorter, Recorder, Key};
+#[cfg(not(test))]
+use sorting::{table_header, table_row};
+#[cfg(test)]
+use sorting::{Record, check_sorter, sort_counting};
+#[cfg(test)]
+use std::rand::{task_rng, Rng};
+
+#[allow(dead_code)]
+mod sorting;
+#[allow(dead_code)]
+mod bubble_sort;
+#[allow(dead_code)]
+mod insertion_sort;
+#[allow(dead_code)]
+mod quick_sort;
+#[allow(dead_code)]
+mod heap_sort;
+#[allow(dead_code)]
+mod merge_sort;
+#[allow(dead_code)]
+mod radix_sort;
+
+// Each sorter sorts by key, which is the only order radix sort knows about
+
+struct BubbleSort;
+
+impl Sorter for BubbleSort {
+    fn name(&self) -> &'static str {
+        "Bubble sort"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        bubble_sort::sort_watched(v, |a, b| a.key().cmp(&b.key()),
+                                  |i, j| recorder.compared(i, j), |i, j| recorder.swapped(i, j));
+    }
+}
+
+struct InsertionSort;
+
+impl Sorter for InsertionSort {
+    fn name(&self) -> &'static str {
+        "Insertion sort"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        insertion_sort::sort_watched(v, |a, b| a.key().cmp(&b.key()),
+                                     |i, j| recorder.compared(i, j),
+                                     |i, j| recorder.swapped(i, j));
+    }
+}
+
+struct QuickSort;
+
+impl Sorter for QuickSort {
+    fn name(&self) -> &'static str {
+        "Quicksort"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        quick_sort::sort_watched(v, |a, b| a.key().cmp(&b.key()),
+                                 |i, j| recorder.compared(i, j), |i, j| recorder.swapped(i, j));
+    }
+}
+
+struct HeapSort;
+
+impl Sorter for HeapSort {
+    fn name(&self) -> &'static str {
+        "Heapsort"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        heap_sort::sort_watched(v, |a, b| a.key().cmp(&b.key()),
+                                |i, j| recorder.compared(i, j), |i, j| recorder.swapped(i, j));
+    }
+}
+
+struct MergeSort;
+
+impl Sorter for MergeSort {
+    fn name(&self) -> &'static str {
+        "Merge sort"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        merge_sort::sort_watched(v, |a, b| a.key().cmp(&b.key()),
+                                 |i, j| recorder.compared(i, j), |i, x| recorder.written(i, x));
+    }
+}
+
+struct RadixSort;
+
+impl Sorter for RadixSort {
+    fn name(&self) -> &'static str {
+        "Radix sort"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>) {
+        radix_sort::sort_watched(v, |x| x.key(), |i, x| recorder.written(i, x));
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let args = std::os::args();
+    let len = if args.len() > 1 {
+        from_str(args.get(1).as_slice()).expect("the length must be a number")
+    } else {
+        1000
+    };
+
+    println!("Sorting {} numbers", len);
+    println!("{}", table_header());
+    println!("{}", table_row(&BubbleSort, len));
+    println!("{}", table_row(&InsertionSort, len));
+    println!("{}", table_row(&QuickSort, len));
+    println!("{}", table_row(&HeapSort, len));
+    println!("{}", table_row(&MergeSort, len));
+    println!("{}", table_row(&RadixSort, len));
+}
+
+#[test]
+fn test_sorters() {
+    check_sorter(&BubbleSort);
+    check_sorter(&InsertionSort);
+    check_sorter(&QuickSort);
+    check_sorter(&HeapSort);
+    check_sorter(&MergeSort);
+    check_sorter(&RadixSort);
+}
+
+#[test]
+fn test_counts() {
+    let mut v = [3i, 1, 2];
+    let counts = sort_counting(&BubbleSort, v);
+    assert_eq!(v.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!((counts.comparisons, counts.swaps, counts.writes), (3, 2, 0));
+
+    let mut v = [3i, 1, 2];
+    let counts = sort_counting(&MergeSort, v);
+    assert_eq!(v.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!(counts.swaps, 0);
+    assert!(counts.writes > 0);
+
+    // Radix sort only writes
+    let mut v = [3i, 1, 2];
+    let counts = sort_counting(&RadixSort, v);
+    assert_eq!(v.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!((counts.comparisons, counts.swaps), (0, 0));
+}
+
+// The keys and original positions of the records, once sorted
+#[cfg(test)]
+fn sort_records<S: Sorter>(sorter: &S, records: &[Record]) -> Vec<(uint, uint)> {
+    let mut records = records.to_vec();
+    sort_counting(sorter, records.as_mut_slice());
+    records.iter().map(|r| (r.key, r.index)).collect()
+}
+
+// Radix sort can't be given an order, so the sorters all sort by key. The
+// stable ones must then agree exactly, records with equal keys included
+#[test]
+fn test_same_order() {
+    let mut rng = task_rng();
+    let records: Vec<Record> = range(0, 300).map(|i| {
+        Record { key: rng.gen_range(0u, 50), index: i }
+    }).collect();
+
+    let expected = sort_records(&MergeSort, records.as_slice());
+    assert_eq!(sort_records(&RadixSort, records.as_slice()), expected);
+    assert_eq!(sort_records(&InsertionSort, records.as_slice()), expected);
+    assert_eq!(sort_records(&BubbleSort, records.as_slice()), expected);
+}
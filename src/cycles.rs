@@ -0,0 +1,47 @@
+// Helpers for the cycles of iterated maps
+// Used by happy_numbers and hailstone
+
+// The length values of the cycle through start, listed from the smallest
+// one, so that the same cycle always compares equal wherever we entered it
+pub fn collect_cycle(start: u64, length: uint, next: |u64| -> u64) -> Vec<u64> {
+    let mut cycle = vec![start];
+    for _ in range(1, length) {
+        let last = *cycle.last().unwrap();
+        cycle.push(next(last));
+    }
+    rotate_to_smallest(cycle.as_slice())
+}
+
+// The same cycle, starting at its smallest value
+pub fn rotate_to_smallest(cycle: &[u64]) -> Vec<u64> {
+    let (smallest, _) = match cycle.iter().enumerate().min_by(|&(_, &x)| x) {
+        Some(found) => found,
+        None => return vec![]
+    };
+    cycle.slice_from(smallest).iter()
+         .chain(cycle.slice_to(smallest).iter())
+         .map(|&x| x)
+         .collect()
+}
+
+// This file is only used as a module
+#[allow(dead_code)]
+#[cfg(not(test))]
+fn main() {}
+
+#[test]
+fn test_rotate_to_smallest() {
+    assert_eq!(rotate_to_smallest(&[4, 2, 1]), vec![1, 4, 2]);
+    assert_eq!(rotate_to_smallest(&[1, 4, 2]), vec![1, 4, 2]);
+    assert_eq!(rotate_to_smallest(&[7]), vec![7]);
+    assert_eq!(rotate_to_smallest(&[]), vec![]);
+}
+
+#[test]
+fn test_collect_cycle() {
+    // The 3x + 1 cycle, entered at 2
+    let step = |x: u64| if x % 2 == 0 { x / 2 } else { 3 * x + 1 };
+    assert_eq!(collect_cycle(2, 3, step), vec![1, 4, 2]);
+    // Squares modulo 7
+    assert_eq!(collect_cycle(4, 2, |x| x * x % 7), vec![2, 4]);
+}
@@ -2,29 +2,186 @@
 extern crate collections;
 
 use std::str::StrSlice;
+use std::io::{IoResult, EndOfFile};
 use collections::hashmap::HashMap;
 
+#[cfg(not(test))]
+use std::io::{File, BufferedReader};
+#[cfg(not(test))]
+use std::os::args;
+
+// Calculates the entropy (in bits) of a distribution given by the number of
+// times each symbol occurred and the total number of symbols
+pub fn entropy_from_counts<I: Iterator<uint>>(counts: I, total: uint) -> f64 {
+    if total == 0 {
+        return 0f64;
+    }
+
+    let len = total as f64;
+    counts.filter(|&nb| nb > 0).fold(0f64, |acc, nb| {
+        let p = (nb as f64) / len;
+        acc - p * p.log2()
+    })
+}
+
+// Returns the entropy of the chars of a string
 pub fn shannon_entropy(s: &str) -> f64 {
     let mut map = HashMap::<char, uint>::new();
+    let mut len = 0u;
 
-    // Count occurrences of each char
+    // Count occurrences of each char. We can't use s.len() as the total,
+    // because it is the length in bytes, not in chars
     for c in s.chars() {
         map.insert_or_update_with(c, 1, |_,v| *v += 1);
+        len += 1;
     }
 
     // Calculate the entropy
-    let len = s.len() as f64;
-    map.iter().fold(0f64, |acc, (_, nb)| {
-        let p = (*nb as f64) / len;
-        acc - p * p.log2()
-    })
+    entropy_from_counts(map.values().map(|&nb| nb), len)
+}
+
+// Returns the entropy of a sequence of bytes
+pub fn byte_entropy(bytes: &[u8]) -> f64 {
+    let mut acc = EntropyAccumulator::new();
+    acc.update(bytes);
+    acc.entropy()
+}
+
+// Calculates the entropy of a stream of bytes which is fed chunk by chunk,
+// so we never need to hold all of the data in memory
+pub struct EntropyAccumulator {
+    counts: [uint, ..256],
+    total: uint
+}
+
+impl EntropyAccumulator {
+    pub fn new() -> EntropyAccumulator {
+        EntropyAccumulator { counts: [0u, ..256], total: 0 }
+    }
+
+    // Adds a chunk of data to the accumulated counts
+    pub fn update(&mut self, chunk: &[u8]) {
+        for &b in chunk.iter() {
+            self.counts[b as uint] += 1;
+        }
+        self.total += chunk.len();
+    }
+
+    // Number of bytes seen so far
+    pub fn total(&self) -> uint {
+        self.total
+    }
+
+    // Entropy of all the bytes seen so far
+    pub fn entropy(&self) -> f64 {
+        entropy_from_counts(self.counts.iter().map(|&nb| nb), self.total)
+    }
+
+    // Forgets all the bytes seen so far
+    pub fn reset(&mut self) {
+        self.counts = [0u, ..256];
+        self.total = 0;
+    }
+}
+
+// Reads from the reader until buf is full or the end of the stream is
+// reached, and returns the number of bytes read
+fn fill_block<R: Reader>(reader: &mut R, buf: &mut [u8]) -> IoResult<uint> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(buf.mut_slice_from(filled)) {
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind == EndOfFile => break,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(filled)
+}
+
+// Iterator over the entropy of each fixed-size block of a stream. Yields the
+// offset of the block and its entropy. The last block may be shorter than
+// the others. Useful to spot compressed or encrypted regions in binary files,
+// since they have an entropy close to 8 bits per byte
+pub struct BlockEntropies<R> {
+    reader: R,
+    buf: Vec<u8>,
+    offset: u64,
+    done: bool
 }
+
+impl<R: Reader> BlockEntropies<R> {
+    pub fn new(reader: R, block_size: uint) -> BlockEntropies<R> {
+        assert!(block_size > 0, "block size must be positive");
+        BlockEntropies {
+            reader: reader,
+            buf: Vec::from_elem(block_size, 0u8),
+            offset: 0,
+            done: false
+        }
+    }
+}
+
+impl<R: Reader> Iterator<IoResult<(u64, f64)>> for BlockEntropies<R> {
+    fn next(&mut self) -> Option<IoResult<(u64, f64)>> {
+        if self.done {
+            return None;
+        }
+
+        let read = match fill_block(&mut self.reader, self.buf.as_mut_slice()) {
+            Ok(n) => n,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+
+        if read < self.buf.len() {
+            self.done = true;
+        }
+        if read == 0 {
+            return None;
+        }
+
+        let offset = self.offset;
+        self.offset += read as u64;
+        Some(Ok((offset, byte_entropy(self.buf.slice_to(read)))))
+    }
+}
+
 // Needed so fibonacci_word compiles cleanly, because fibonacci_word
 // uses this code as a library
 #[allow(dead_code)]
 #[cfg(not(test))]
 fn main() {
-    println!("{:f}", shannon_entropy("1223334444"));
+    let args = args();
+
+    // Without arguments we just solve the Rosetta task. Otherwise we print
+    // the entropy of each block of the given file:
+    //     entropy <file> [block size]
+    if args.len() < 2 {
+        println!("{:f}", shannon_entropy("1223334444"));
+        return;
+    }
+
+    let path = Path::new(args.get(1).as_slice());
+    let block_size = match args.len() {
+        2 => 4096,
+        _ => from_str::<uint>(args.get(2).as_slice())
+                 .expect("the block size must be a positive integer")
+    };
+
+    let file = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) => fail!("couldn't open {}: {}", path.display(), e)
+    };
+
+    println!("{:>12s} {}", "offset", "entropy");
+    for block in BlockEntropies::new(BufferedReader::new(file), block_size) {
+        match block {
+            Ok((offset, entropy)) => println!("{:>12u} {:.6f}", offset, entropy),
+            Err(e) => fail!("error while reading {}: {}", path.display(), e)
+        }
+    }
 }
 
 
@@ -43,5 +200,51 @@ fn test_entropy() {
     for (input, expected) in tests.move_iter() {
         let output = shannon_entropy(input);
         assert!((output - expected).abs() < epsilon);
+        // For ASCII, chars and bytes are the same thing
+        assert!((byte_entropy(input.as_bytes()) - expected).abs() < epsilon);
     }
 }
+
+#[test]
+fn test_non_ascii() {
+    let epsilon: f64 = 0.0000001;
+
+    // Two different chars, but 'é' takes two bytes
+    assert!((shannon_entropy("aé") - 1.0).abs() < epsilon);
+    assert!((byte_entropy("aé".as_bytes()) - 1.584962500721).abs() < epsilon);
+
+    // A single repeated char has no entropy, even if it takes several bytes
+    assert_eq!(shannon_entropy("ßßßß"), 0.0);
+    assert_eq!(shannon_entropy(""), 0.0);
+    assert_eq!(byte_entropy([]), 0.0);
+}
+
+#[test]
+fn test_accumulator() {
+    let data = "1223334444555555555".as_bytes();
+    let mut acc = EntropyAccumulator::new();
+    for chunk in data.chunks(4) {
+        acc.update(chunk);
+    }
+    assert_eq!(acc.total(), data.len());
+    assert!((acc.entropy() - byte_entropy(data)).abs() < 0.0000001);
+
+    acc.reset();
+    assert_eq!(acc.total(), 0);
+    assert_eq!(acc.entropy(), 0.0);
+}
+
+#[test]
+fn test_block_entropies() {
+    use std::io::MemReader;
+
+    // One block with a single value, one with all 256 values and a short one
+    let mut data = Vec::from_elem(256, 7u8);
+    data.extend(range(0u, 256).map(|b| b as u8));
+    data.push_all([1, 2]);
+
+    let blocks: Vec<(u64, f64)> = BlockEntropies::new(MemReader::new(data), 256)
+                                      .map(|b| b.unwrap())
+                                      .collect();
+    assert_eq!(blocks, vec![(0, 0.0), (256, 8.0), (512, 1.0)]);
+}
@@ -1,6 +1,11 @@
 // Implements http://rosettacode.org/wiki/Factorial
+// and http://rosettacode.org/wiki/Multifactorial
+extern crate num;
 
-use std::iter::range_inclusive;
+use std::iter::{range_inclusive, range_step};
+use std::num::{One, Zero, FromPrimitive};
+use num::bigint::BigUint;
+use num::Integer;
 
 // Calculate the factorial using recursion
 fn factorial_recursive (n: uint) -> uint {
@@ -24,6 +29,169 @@ fn factorial_loop(n: uint) -> uint {
     fac
 }
 
+// Calculate the factorial as a u64, or None if it doesn't fit
+fn factorial_checked(n: u64) -> Option<u64> {
+    range_inclusive(1, n).fold(Some(1u64), |p, t| p.and_then(|p| p.checked_mul(&t)))
+}
+
+fn big(n: u64) -> BigUint {
+    FromPrimitive::from_u64(n).unwrap()
+}
+
+// Calculate the factorial as a BigUint, one multiplication at a time
+fn factorial_naive(n: u64) -> BigUint {
+    range_inclusive(1, n).fold(One::one(), |p: BigUint, t| p * big(t))
+}
+
+// The product of the numbers in (lo, hi], splitting the range in halves so
+// that we multiply numbers of similar sizes, which is much faster than
+// multiplying a huge number by small ones
+fn range_product(lo: u64, hi: u64) -> BigUint {
+    if hi - lo <= 8 {
+        return range_inclusive(lo + 1, hi).fold(One::one(), |p: BigUint, t| p * big(t));
+    }
+    let mid = lo + (hi - lo) / 2;
+    range_product(lo, mid) * range_product(mid, hi)
+}
+
+// The product of some numbers, by binary splitting
+fn product(values: &[u64]) -> BigUint {
+    match values.len() {
+        0 => One::one(),
+        1 => big(values[0]),
+        len => product(values.slice_to(len / 2)) * product(values.slice_from(len / 2))
+    }
+}
+
+// Calculate the factorial by binary splitting
+fn factorial_binary_splitting(n: u64) -> BigUint {
+    range_product(0, n)
+}
+
+// The prime numbers up to n
+fn primes_up_to(n: u64) -> Vec<u64> {
+    let mut is_prime = Vec::from_elem(n as uint + 1, true);
+    let mut primes = vec![];
+    for p in range_inclusive(2, n) {
+        if *is_prime.get(p as uint) {
+            primes.push(p);
+            for multiple in range_step(p * p, n + 1, p) {
+                *is_prime.get_mut(multiple as uint) = false;
+            }
+        }
+    }
+    primes
+}
+
+// The swinging factorial n! / (n / 2)!^2. The exponent of a prime p in it
+// is the number of odd values among n / p, n / p^2, ..., and p to that
+// exponent is never more than n
+fn swing(n: u64, primes: &[u64]) -> BigUint {
+    let mut factors = vec![];
+    for &p in primes.iter().take_while(|&&p| p <= n) {
+        let mut q = n;
+        let mut power = 1;
+        loop {
+            q /= p;
+            if q == 0 {
+                break;
+            }
+            if q % 2 == 1 {
+                power *= p;
+            }
+        }
+        if power > 1 {
+            factors.push(power);
+        }
+    }
+    product(factors.as_slice())
+}
+
+fn prime_swing(n: u64, primes: &[u64]) -> BigUint {
+    if n < 2 {
+        return One::one();
+    }
+    let half = prime_swing(n / 2, primes);
+    half * half * swing(n, primes)
+}
+
+// Calculate the factorial with Luschny's prime swing algorithm, from
+// n! = (n / 2)!^2 * swing(n)
+fn factorial_prime_swing(n: u64) -> BigUint {
+    prime_swing(n, primes_up_to(n).as_slice())
+}
+
+// The numbers multiplied in the multifactorial of degree k: n, n - k, ...
+fn multifactorial_terms(n: u64, k: u64) -> Vec<u64> {
+    assert!(k > 0, "the degree must be positive");
+    let mut terms = vec![];
+    let mut t = n;
+    while t > 0 {
+        terms.push(t);
+        if t <= k {
+            break;
+        }
+        t -= k;
+    }
+    terms
+}
+
+// Calculate the multifactorial of degree k: n!! for k = 2, n!!! for k = 3...
+fn multifactorial(n: u64, k: u64) -> BigUint {
+    product(multifactorial_terms(n, k).as_slice())
+}
+
+// Calculate the multifactorial as a u64, or None if it doesn't fit
+fn multifactorial_checked(n: u64, k: u64) -> Option<u64> {
+    multifactorial_terms(n, k).iter().fold(Some(1u64), |p, &t| p.and_then(|p| p.checked_mul(&t)))
+}
+
+// Calculate the binomial coefficient from its prime factorization: by
+// Legendre's formula the exponent of p is the sum over the powers q of p of
+// n / q - k / q - (n - k) / q
+fn binomial(n: u64, k: u64) -> BigUint {
+    if k > n {
+        return Zero::zero();
+    }
+    let mut factors = vec![];
+    for &p in primes_up_to(n).iter() {
+        let mut q = p;
+        loop {
+            let exponent = n / q - k / q - (n - k) / q;
+            for _ in range(0, exponent) {
+                factors.push(p);
+            }
+            // Stop before q * p overflows
+            if q > n / p {
+                break;
+            }
+            q *= p;
+        }
+    }
+    product(factors.as_slice())
+}
+
+// Calculate the binomial coefficient as a u64, or None if it doesn't fit.
+// After i steps we have C(n - k + i, i), which is never more than the
+// result, and dividing by the gcd first keeps the products exact
+fn binomial_checked(n: u64, k: u64) -> Option<u64> {
+    if k > n {
+        return Some(0);
+    }
+    let k = std::cmp::min(k, n - k);
+    let mut result = 1u64;
+    for i in range_inclusive(1, k) {
+        let g = result.gcd(&i);
+        // i / g divides n - k + i, because it divides the product and is
+        // coprime to result / g
+        result = match (result / g).checked_mul(&((n - k + i) / (i / g))) {
+            Some(r) => r,
+            None => return None
+        };
+    }
+    Some(result)
+}
+
 #[cfg(not(test))]
 fn main () {
 	for i in range(1u, 10) {
@@ -35,6 +203,17 @@ fn main () {
     for i in range(1u, 10) {
         println!("{}", factorial_loop(i));
     }
+
+    // Beyond 20! a u64 isn't enough
+    println!("20! = {}, 21! = {}", factorial_checked(20), factorial_checked(21));
+    println!("100! = {}", factorial_prime_swing(100));
+
+    for k in range_inclusive(1u64, 5) {
+        let row: Vec<BigUint> = range_inclusive(1u64, 10).map(|n| multifactorial(n, k)).collect();
+        println!("Degree {}: {}", k, row);
+    }
+
+    println!("C(100, 50) = {}", binomial(100, 50));
 }
 
 // Some tests and benchmarks
@@ -43,6 +222,11 @@ mod tests {
     extern crate test;
     use self::test::Bencher;
     use super::{factorial_recursive, factorial_iterative, factorial_loop};
+    use super::{factorial_checked, factorial_naive, factorial_binary_splitting};
+    use super::{factorial_prime_swing, multifactorial, multifactorial_checked};
+    use super::{binomial, binomial_checked, big};
+    use num::bigint::BigUint;
+    use std::iter::range_inclusive;
 
     // Tests
     #[test]
@@ -63,6 +247,62 @@ mod tests {
         assert!(factorial_loop(10) == 3628800);
     }
 
+    #[test]
+    fn test_fac_checked() {
+        assert_eq!(factorial_checked(0), Some(1));
+        assert_eq!(factorial_checked(20), Some(2432902008176640000));
+        assert_eq!(factorial_checked(21), None);
+    }
+
+    #[test]
+    fn test_fac_big() {
+        let expected: BigUint = from_str("15511210043330985984000000").unwrap();
+        assert_eq!(factorial_prime_swing(25), expected);
+
+        for n in range(0u64, 300) {
+            let naive = factorial_naive(n);
+            assert_eq!(factorial_binary_splitting(n), naive);
+            assert_eq!(factorial_prime_swing(n), naive);
+        }
+
+        let big_fac = factorial_prime_swing(10000).to_str();
+        assert_eq!(big_fac.len(), 35660);
+        assert!(big_fac.as_slice().starts_with("28462596809170545189"));
+        assert_eq!(factorial_binary_splitting(10000), factorial_prime_swing(10000));
+    }
+
+    #[test]
+    fn test_multifactorial() {
+        // From http://rosettacode.org/wiki/Multifactorial
+        let double: Vec<u64> = range_inclusive(1u64, 10)
+                                   .map(|n| multifactorial_checked(n, 2).unwrap())
+                                   .collect();
+        assert_eq!(double, vec![1, 2, 3, 8, 15, 48, 105, 384, 945, 3840]);
+        assert_eq!(multifactorial(10, 3), big(280));
+        assert_eq!(multifactorial(0, 2), big(1));
+        assert_eq!(multifactorial(20, 1), factorial_naive(20));
+        assert_eq!(multifactorial_checked(40, 2), None);
+        assert_eq!(multifactorial(40, 2).to_str(), "2551082656125828464640000".to_string());
+    }
+
+    #[test]
+    fn test_binomial() {
+        assert_eq!(binomial(5, 2), big(10));
+        assert_eq!(binomial(5, 6), big(0));
+        assert_eq!(binomial(100, 50).to_str(), "100891344545564193334812497256".to_string());
+
+        for n in range(0u64, 60) {
+            for k in range_inclusive(0, n) {
+                let expected = factorial_naive(n) / (factorial_naive(k) * factorial_naive(n - k));
+                assert_eq!(binomial(n, k), expected);
+                assert_eq!(binomial_checked(n, k).map(big), Some(expected));
+            }
+        }
+
+        assert_eq!(binomial_checked(67, 33), Some(14226520737620288370));
+        assert_eq!(binomial_checked(68, 34), None);
+    }
+
     // Benchmarks
     #[bench]
     fn bench_fac_recursive(b: &mut Bencher) {
@@ -84,4 +324,55 @@ mod tests {
             test::black_box(factorial_loop(10));
         });
     }
+
+    #[bench]
+    fn bench_fac_naive_10_4(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(factorial_naive(10000));
+        });
+    }
+
+    // There is no bench_fac_naive_10_5, because it takes far too long
+
+    #[bench]
+    fn bench_fac_binary_splitting_10_4(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(factorial_binary_splitting(10000));
+        });
+    }
+
+    #[bench]
+    fn bench_fac_binary_splitting_10_5(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(factorial_binary_splitting(100000));
+        });
+    }
+
+    #[bench]
+    fn bench_fac_prime_swing_10_4(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(factorial_prime_swing(10000));
+        });
+    }
+
+    #[bench]
+    fn bench_fac_prime_swing_10_5(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(factorial_prime_swing(100000));
+        });
+    }
+
+    #[bench]
+    fn bench_binomial_10_4(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(binomial(10000, 5000));
+        });
+    }
+
+    #[bench]
+    fn bench_binomial_10_5(b: &mut Bencher) {
+        b.iter(|| {
+            test::black_box(binomial(100000, 50000));
+        });
+    }
 }
@@ -1,38 +1,241 @@
 // Implements http://rosettacode.org/wiki/Fibonacci_word
+// and http://rosettacode.org/wiki/Fibonacci_word/fractal
 extern crate collections;
 
-use entropy::shannon_entropy;
+use entropy::entropy_from_counts;
+use std::io::IoResult;
 use std::iter::range_inclusive;
+use std::cmp::{min, max};
+use std::num::CheckedAdd;
+
+#[cfg(test)]
+use entropy::shannon_entropy;
+#[cfg(not(test))]
+use std::os::args;
+#[cfg(not(test))]
+use std::io::stdout;
+
+// We only need entropy_from_counts (and shannon_entropy in the tests)
+// from the entropy module
+#[allow(dead_code)]
 mod entropy;
 
+// Lazy view of the k-th fibonacci word. The word is never built: its
+// characters are found by walking down the recursive definition
+// F(1) = "1", F(2) = "0", F(k) = F(k - 1) + F(k - 2)
+pub struct FibonacciWord {
+    k: uint,
+    // lengths.get(i) is the length of F(i). Lengths that don't fit in a u64
+    // are saturated, since no index can reach past them anyway
+    lengths: Vec<u64>
+}
+
+impl FibonacciWord {
+    pub fn new(k: uint) -> FibonacciWord {
+        assert!(k > 0, "fibonacci words are numbered from 1");
+        let mut lengths = vec![0u64, 1, 1];
+        for i in range_inclusive(3, k) {
+            let len = lengths.get(i - 1).checked_add(lengths.get(i - 2))
+                             .unwrap_or(std::u64::MAX);
+            lengths.push(len);
+        }
+        FibonacciWord { k: k, lengths: lengths }
+    }
+
+    pub fn len(&self) -> u64 {
+        *self.lengths.get(self.k)
+    }
+
+    // Returns the character at index i (starting at 0) of the word
+    pub fn char_at(&self, mut i: u64) -> Option<char> {
+        if i >= self.len() {
+            return None;
+        }
+
+        // F(k) is F(k - 1) followed by F(k - 2), so we go down into the
+        // half containing the index until we reach one of the base words
+        let mut k = self.k;
+        while k > 2 {
+            let left = *self.lengths.get(k - 1);
+            if i < left {
+                k -= 1;
+            } else {
+                i -= left;
+                k -= 2;
+            }
+        }
+
+        Some(if k == 1 { '1' } else { '0' })
+    }
+
+    // Iterates over the characters of the word, using O(k) memory
+    pub fn chars(&self) -> FibonacciChars {
+        FibonacciChars { stack: vec![self.k] }
+    }
+}
+
+// Iterator over the characters of a fibonacci word. The stack holds the
+// words which still have to be produced, the next one on top
+pub struct FibonacciChars {
+    stack: Vec<uint>
+}
+
+impl Iterator<char> for FibonacciChars {
+    fn next(&mut self) -> Option<char> {
+        loop {
+            match self.stack.pop() {
+                None => return None,
+                Some(1) => return Some('1'),
+                Some(2) => return Some('0'),
+                Some(k) => {
+                    self.stack.push(k - 2);
+                    self.stack.push(k - 1);
+                }
+            }
+        }
+    }
+}
+
 // Returns "amount" fibonacci words as a vector of tuples
 // The first value of the tuple is the length of the word
 // and the second one its entropy
 fn fib_words(amount: uint) -> Vec<(uint, f64)> {
     let mut data = Vec::with_capacity(amount);
-    let mut previous = String::from_str("1");
-    let mut next = String::from_str("0");
+
+    // We only need to know how many ones and zeros each word has, which
+    // follow the same recurrence as the words themselves
+    let (mut previous_ones, mut previous_zeros) = (1u, 0u);
+    let (mut ones, mut zeros) = (0u, 1u);
 
     // The first two words (we need to add them manually because
     // they are the base of the sequence)
-    data.push((previous.len(), shannon_entropy(previous.as_slice())));
-    data.push((next.len(), shannon_entropy(next.as_slice())));
+    data.push((1, entropy_from_counts(vec![previous_ones, previous_zeros].move_iter(), 1)));
+    data.push((1, entropy_from_counts(vec![ones, zeros].move_iter(), 1)));
 
     // The rest of the words
     for _ in range_inclusive(3, amount) {
-        let temp = next.clone();
-        next.push_str(previous.as_slice());
-        previous = temp;
-        data.push((next.len(), shannon_entropy(next.as_slice())));
+        let (temp_ones, temp_zeros) = (ones, zeros);
+        ones += previous_ones;
+        zeros += previous_zeros;
+        previous_ones = temp_ones;
+        previous_zeros = temp_zeros;
+
+        let len = ones + zeros;
+        data.push((len, entropy_from_counts(vec![ones, zeros].move_iter(), len)));
     }
 
     data
 }
+
+// Returns the points visited when drawing the fibonacci word fractal of
+// the k-th word. For the n-th character we draw a segment forward, and then
+// if the character is a '0' we turn left if n is even and right if n is odd
+pub fn fractal_points(k: uint) -> Vec<(int, int)> {
+    let word = FibonacciWord::new(k);
+    let mut points = Vec::with_capacity(word.len() as uint + 1);
+    let (mut x, mut y) = (0i, 0i);
+    let (mut dx, mut dy) = (0i, 1i);
+
+    points.push((x, y));
+    for (i, c) in word.chars().enumerate() {
+        x += dx;
+        y += dy;
+        points.push((x, y));
+
+        if c == '0' {
+            let (ndx, ndy) = if (i + 1) % 2 == 0 { (-dy, dx) } else { (dy, -dx) };
+            dx = ndx;
+            dy = ndy;
+        }
+    }
+
+    points
+}
+
+// Returns the smallest and largest coordinates of the points as
+// (min_x, min_y, max_x, max_y)
+fn bounds(points: &[(int, int)]) -> (int, int, int, int) {
+    points.iter().fold((0, 0, 0, 0), |(x0, y0, x1, y1), &(x, y)| {
+        (min(x0, x), min(y0, y), max(x1, x), max(y1, y))
+    })
+}
+
+// Renders the fractal as an SVG document. Every segment is scale pixels long
+pub fn fractal_svg(points: &[(int, int)], scale: uint) -> String {
+    let (min_x, min_y, max_x, max_y) = bounds(points);
+    // Leave a margin of one unit around the drawing
+    let (width, height) = (max_x - min_x + 2, max_y - min_y + 2);
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                           width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n",
+                          width as uint * scale, height as uint * scale,
+                          min_x - 1, -max_y - 1, width, height);
+    svg.push_str("<polyline fill=\"none\" stroke=\"black\" \
+                  stroke-width=\"0.25\" points=\"");
+    for &(x, y) in points.iter() {
+        // SVG's y axis points down
+        svg.push_str(format!("{},{} ", x, -y).as_slice());
+    }
+    svg.push_str("\"/>\n</svg>\n");
+    svg
+}
+
+// Writes the fractal as a plain PBM image. Every segment is two pixels long,
+// so that parallel segments next to each other don't touch
+pub fn write_fractal_pbm<W: Writer>(points: &[(int, int)], w: &mut W) -> IoResult<()> {
+    let (min_x, min_y, max_x, max_y) = bounds(points);
+    // Leave a margin of one pixel around the drawing
+    let width = (2 * (max_x - min_x) + 3) as uint;
+    let height = (2 * (max_y - min_y) + 3) as uint;
+    let mut pixels = Vec::from_elem(width * height, false);
+
+    // The top row of the image is the largest y
+    let index = |x: int, y: int| {
+        let col = (x - 2 * min_x + 1) as uint;
+        let row = (2 * max_y - y + 1) as uint;
+        row * width + col
+    };
+    for segment in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+        // Both ends of the segment and the pixel between them
+        for &(x, y) in [(2 * x0, 2 * y0), (x0 + x1, y0 + y1), (2 * x1, 2 * y1)].iter() {
+            *pixels.get_mut(index(x, y)) = true;
+        }
+    }
+
+    try!(writeln!(w, "P1"));
+    try!(writeln!(w, "{} {}", width, height));
+    for row in pixels.as_slice().chunks(width) {
+        // Lines of a plain PBM file shouldn't be longer than 70 characters
+        for line in row.chunks(70) {
+            let digits: String = line.iter().map(|&p| if p { '1' } else { '0' }).collect();
+            try!(writeln!(w, "{}", digits));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(not(test))]
 fn main() {
-    println!("Calculating... This may take a couple of minutes...\n");
+    let args = args();
 
-    let words = fib_words(18);
+    // With arguments we draw the fractal instead:
+    //     fibonacci_word (svg|pbm) <word number>
+    if args.len() == 3 {
+        let k = from_str::<uint>(args.get(2).as_slice())
+                    .expect("the word number must be a positive integer");
+        let points = fractal_points(k);
+        let mut out = stdout();
+        let result = match args.get(1).as_slice() {
+            "svg" => out.write_str(fractal_svg(points.as_slice(), 2).as_slice()),
+            "pbm" => write_fractal_pbm(points.as_slice(), &mut out),
+            other => fail!("unknown format {}, expected svg or pbm", other)
+        };
+        result.unwrap();
+        return;
+    }
+
+    let words = fib_words(37);
     let mut i = 1;
 
     println!("{:>2}:{:>10} {}", "N", "length", "entropy");
@@ -41,6 +244,24 @@ fn main() {
         i += 1;
     }
 }
+
+// Builds the k-th fibonacci word the straightforward way
+#[cfg(test)]
+fn build_word(k: uint) -> String {
+    let mut previous = String::from_str("1");
+    let mut next = String::from_str("0");
+    if k == 1 {
+        return previous;
+    }
+
+    for _ in range(2, k) {
+        let temp = next.clone();
+        next.push_str(previous.as_slice());
+        previous = temp;
+    }
+    next
+}
+
 #[test]
 fn test_fibonacii_words() {
     let expected = vec![
@@ -72,3 +293,61 @@ fn test_fibonacii_words() {
              assert!((output_entropy - expected_entropy).abs() < epsilon);
     }
 }
+
+#[test]
+fn test_lazy_words() {
+    assert_eq!(build_word(1).as_slice(), "1");
+    assert_eq!(build_word(2).as_slice(), "0");
+    assert_eq!(build_word(5).as_slice(), "01001");
+
+    for k in range_inclusive(1u, 15) {
+        let word = FibonacciWord::new(k);
+        let built = build_word(k);
+        let chars: String = word.chars().collect();
+
+        assert_eq!(word.len(), built.len() as u64);
+        assert_eq!(chars, built);
+        for (i, c) in built.as_slice().chars().enumerate() {
+            assert_eq!(word.char_at(i as u64), Some(c));
+        }
+        assert_eq!(word.char_at(word.len()), None);
+
+        let (length, entropy) = *fib_words(k).last().unwrap();
+        assert_eq!(length, built.len());
+        assert!((entropy - shannon_entropy(built.as_slice())).abs() < 0.0000000001);
+    }
+}
+
+#[test]
+fn test_huge_word() {
+    // Far too long to ever be built, its length doesn't even fit in a u64
+    let word = FibonacciWord::new(200);
+    assert_eq!(word.len(), std::u64::MAX);
+
+    // Every word starts with the word two steps before it
+    let small = FibonacciWord::new(20);
+    for i in range(0, small.len()) {
+        assert_eq!(word.char_at(i), small.char_at(i));
+    }
+}
+
+#[test]
+fn test_fractal() {
+    let points = fractal_points(5);
+    // "01001": one segment per character
+    assert_eq!(points, vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 0), (3, 0)]);
+
+    let svg = fractal_svg(points.as_slice(), 1);
+    assert!(svg.as_slice().starts_with("<svg"));
+    assert!(svg.as_slice().contains("points=\"0,0 0,-1 1,-1 2,-1 2,0 3,0 \""));
+
+    let mut pbm = std::io::MemWriter::new();
+    write_fractal_pbm(points.as_slice(), &mut pbm).unwrap();
+    let expected = "P1\n9 5\n\
+                    000000000\n\
+                    011111000\n\
+                    010001000\n\
+                    010001110\n\
+                    000000000\n";
+    assert_eq!(std::str::from_utf8(pbm.get_ref()).unwrap(), expected);
+}
@@ -1,4 +1,23 @@
 // Implements http://rosettacode.org/wiki/Hailstone_sequence
+// and finds the starting values with record chain lengths,
+// http://oeis.org/A006877
+
+extern crate sync;
+extern crate collections;
+extern crate num;
+
+use std::num::{CheckedAdd, CheckedMul, FromPrimitive, ToPrimitive, One};
+use sync::Arc;
+use num::bigint::BigUint;
+use num::Integer;
+use map_reduce::MapReduce;
+use cycles::collect_cycle;
+
+// We only use run from the map-reduce
+#[allow(dead_code)]
+mod map_reduce;
+#[allow(dead_code)]
+mod cycles;
 
 // Define a struct which we can make into an interator.
 struct Hailstone {
@@ -36,19 +55,264 @@ impl Iterator<uint> for Hailstone {
       },
       x               => {
         // All remaining numbers are odd.
-        self.current = (3 * x) + 1;
+        self.current = match next_value(x as u64).and_then(|y| y.to_uint()) {
+          Some(y) => y,
+          None    => fail!("the sequence from {} doesn't fit in a uint", self.start)
+        };
         Some(current)
       },
     }
   }
 }
 
-// Returns the number and length of the longest hailstone sequence up to `limit`
-fn biggest_hailstone(limit: uint) -> (uint, uint) {
-    let mut biggest = range(0u, limit).map(Hailstone::new)
-                                      .max_by(|&mut x| x.len())
-                                      .unwrap();
-    (biggest.start, biggest.len())
+// The value after x, or None if 3 * x + 1 doesn't fit in a u64
+fn next_value(x: u64) -> Option<u64> {
+    CollatzMap::hailstone().step(x)
+}
+
+// The number of values in the sequence from start, including start and 1.
+// cache.get(n) is that length for the n below cache.len(), or 0 if we
+// don't know it yet. 0 isn't part of any sequence, so its length is 0
+fn chain_length(start: u64, cache: &[u32]) -> u64 {
+    if start == 0 {
+        return 0;
+    }
+    let mut x = start;
+    let mut steps = 0;
+    loop {
+        if x < cache.len() as u64 && cache[x as uint] != 0 {
+            return steps + cache[x as uint] as u64;
+        }
+        x = match next_value(x) {
+            Some(y) => y,
+            None => {
+                let big: BigUint = FromPrimitive::from_u64(x).unwrap();
+                return steps + big_chain_length(big, cache);
+            }
+        };
+        steps += 1;
+    }
+}
+
+// Same as chain_length for values which don't fit in a u64. We go back
+// to u64 as soon as the value is small enough
+fn big_chain_length(start: BigUint, cache: &[u32]) -> u64 {
+    let one: BigUint = One::one();
+    let three: BigUint = FromPrimitive::from_uint(3).unwrap();
+    let mut x = start;
+    let mut steps = 0;
+    loop {
+        match x.to_u64() {
+            Some(small) => return steps + chain_length(small, cache),
+            None => {}
+        }
+        x = if x.is_even() { x >> 1 } else { x * three + one };
+        steps += 1;
+    }
+}
+
+// The chain lengths of the numbers below size, each one computed from
+// the lengths of the smaller numbers
+fn length_cache(size: uint) -> Vec<u32> {
+    let mut cache = Vec::from_elem(std::cmp::max(size, 2), 0u32);
+    *cache.get_mut(1) = 1;
+    for n in range(2, cache.len()) {
+        let length = chain_length(n as u64, cache.slice_to(n));
+        *cache.get_mut(n) = length as u32;
+    }
+    cache
+}
+
+// The starting values in [lo, hi) whose chain is longer than those of the
+// smaller values in the range, with their lengths
+fn local_records(job: (u64, u64, Arc<Vec<u32>>)) -> Vec<(u64, u64)> {
+    let (lo, hi, cache) = job;
+    let mut records = vec![];
+    let mut best = 0;
+    for n in range(lo, hi) {
+        let length = chain_length(n, cache.as_slice());
+        if length > best {
+            best = length;
+            records.push((n, length));
+        }
+    }
+    records
+}
+
+// Numbers in each range sent to the workers
+static RANGE_SIZE: u64 = 1 << 16;
+
+// Lengths cached by the search, 16MB worth of them at most
+static MAX_CACHE: u64 = 1 << 22;
+
+// Returns the starting values below limit whose chain is longer than the
+// chain of every smaller starting value, with their lengths. The range is
+// split between worker tasks, which share a cache of the lengths of the
+// small numbers. Every worker finds the records of its own ranges, and
+// reducing them in order leaves only the records of the whole search
+pub fn hailstone_records(limit: u64) -> Vec<(u64, u64)> {
+    let cache = Arc::new(length_cache(std::cmp::min(limit, MAX_CACHE) as uint));
+
+    let mut jobs = vec![];
+    let mut lo = 1;
+    while lo < limit {
+        let hi = std::cmp::min(limit, lo + RANGE_SIZE);
+        jobs.push((lo, hi, cache.clone()));
+        lo = hi;
+    }
+
+    MapReduce::new().chunk_size(1)
+                    .run(jobs, local_records, vec![], |mut records: Vec<(u64, u64)>, local| {
+        for (n, length) in local.move_iter() {
+            if records.last().map_or(true, |&(_, best)| length > best) {
+                records.push((n, length));
+            }
+        }
+        records
+    }).unwrap()
+}
+
+// The generalised Collatz map: x / 2 for even x, and a * x + b for odd x.
+// The hailstone sequence uses a = 3 and b = 1
+pub struct CollatzMap {
+    a: u64,
+    b: u64
+}
+
+// How the iteration of a map from some start value ends
+#[deriving(PartialEq, Show)]
+pub enum Ending {
+    ReachesOne,
+    // The cycle, starting at its smallest value
+    EntersCycle(Vec<u64>),
+    // Some value doesn't fit in a u64
+    Overflows
+}
+
+#[deriving(PartialEq, Show)]
+pub struct Statistics {
+    pub start: u64,
+    pub ending: Ending,
+    // Steps until we reach 1, the first repeated value or the last value
+    // before an overflow
+    pub steps: uint,
+    // The steps to reach 1, if it does
+    pub total_stopping_time: Option<uint>,
+    // The steps until the value first drops below the start, if it does
+    pub stopping_time: Option<uint>,
+    pub peak: u64,
+    pub odd_steps: uint,
+    pub even_steps: uint
+}
+
+impl CollatzMap {
+    pub fn new(a: u64, b: u64) -> CollatzMap {
+        CollatzMap { a: a, b: b }
+    }
+
+    pub fn hailstone() -> CollatzMap {
+        CollatzMap::new(3, 1)
+    }
+
+    // The value after x, or None if it doesn't fit in a u64
+    pub fn step(&self, x: u64) -> Option<u64> {
+        if x % 2 == 0 {
+            Some(x / 2)
+        } else {
+            x.checked_mul(&self.a).and_then(|y| y.checked_add(&self.b))
+        }
+    }
+
+    // Finds how the iteration from start ends, and after how many steps.
+    // The cycles are found with Brent's algorithm, so that we don't have
+    // to remember every value
+    fn ending(&self, start: u64) -> (Ending, uint) {
+        let mut x = start;
+        let mut steps = 0u;
+        // The tortoise waits at the value of the last power of two, until
+        // the hare comes back to it or moves a power of two further
+        let mut tortoise = start;
+        let mut power = 1u;
+        let mut length = 0u;
+        loop {
+            if x == 1 {
+                return (ReachesOne, steps);
+            }
+            x = match self.step(x) {
+                Some(y) => y,
+                None => return (Overflows, steps)
+            };
+            steps += 1;
+            length += 1;
+            if x == tortoise {
+                break;
+            }
+            if length == power {
+                tortoise = x;
+                power *= 2;
+                length = 0;
+            }
+        }
+
+        // The cycle has length values. With the hare that far ahead, the
+        // tortoise and the hare meet where the cycle starts
+        let mut tail = 0u;
+        let mut tortoise = start;
+        let mut hare = start;
+        for _ in range(0, length) {
+            hare = self.step(hare).unwrap();
+        }
+        while tortoise != hare {
+            tortoise = self.step(tortoise).unwrap();
+            hare = self.step(hare).unwrap();
+            tail += 1;
+        }
+
+        let cycle = collect_cycle(tortoise, length, |x| self.step(x).unwrap());
+        (EntersCycle(cycle), tail + length)
+    }
+
+    pub fn statistics(&self, start: u64) -> Statistics {
+        let (ending, steps) = self.ending(start);
+
+        let mut x = start;
+        let mut peak = start;
+        let mut odd_steps = 0;
+        let mut even_steps = 0;
+        let mut stopping_time = None;
+        for i in range(0, steps) {
+            if x % 2 == 0 {
+                even_steps += 1;
+            } else {
+                odd_steps += 1;
+            }
+            // ending already checked that we don't overflow
+            x = self.step(x).unwrap();
+            peak = std::cmp::max(peak, x);
+            if stopping_time.is_none() && x < start {
+                stopping_time = Some(i + 1);
+            }
+        }
+
+        Statistics {
+            start: start,
+            total_stopping_time: if ending == ReachesOne { Some(steps) } else { None },
+            ending: ending,
+            steps: steps,
+            stopping_time: stopping_time,
+            peak: peak,
+            odd_steps: odd_steps,
+            even_steps: even_steps
+        }
+    }
+}
+
+// Returns the number and length of the longest hailstone sequence up to `limit`,
+// or None if there are no starting values below it
+fn biggest_hailstone(limit: uint) -> Option<(uint, uint)> {
+    hailstone_records(limit as u64).last().map(|&(biggest, length)| {
+        (biggest as uint, length as uint)
+    })
 }
 
 #[cfg(not(test))]
@@ -63,8 +327,25 @@ fn main() {
           two_seven.slice(ts_len - 4, ts_len));
 
   // Find the longest.
-  let (biggest, length) = biggest_hailstone(100000);
+  let (biggest, length) = biggest_hailstone(100000).unwrap();
   println!("Largest: {}, Size: {}", biggest, length);
+
+  // The records below a limit given as argument, 10^7 by default. Below
+  // 10^10 takes a while even with every core busy
+  let args = std::os::args();
+  let limit = if args.len() > 1 {
+      from_str(args.get(1).as_slice()).expect("the limit must be a number")
+  } else {
+      10000000
+  };
+  for &(n, length) in hailstone_records(limit).iter() {
+      println!("{}: {}", n, length);
+  }
+
+  // A few starting values of the 3x+1 and 5x+1 maps
+  for &(a, start) in [(3u64, 27u64), (3, 97), (5, 5), (5, 17), (5, 7)].iter() {
+      println!("{}x+1 from {}: {}", a, start, CollatzMap::new(a, 1).statistics(start));
+  }
 }
 
 #[test]
@@ -77,7 +358,110 @@ fn test_27() {
 
 #[test]
 fn test_biggest() {
-    let (biggest, length) = biggest_hailstone(100000);
-    assert_eq!(biggest, 77031);
-    assert_eq!(length, 351);
+    assert_eq!(biggest_hailstone(100000), Some((77031, 351)));
+    assert_eq!(biggest_hailstone(2), Some((1, 1)));
+    // There are no starting values below 1
+    assert_eq!(biggest_hailstone(1), None);
+    assert_eq!(biggest_hailstone(0), None);
+}
+
+#[test]
+#[should_fail]
+fn test_iterator_overflow() {
+    // 3 * x + 1 doesn't fit in a uint
+    Hailstone::new(std::uint::MAX).count();
+}
+
+#[test]
+fn test_overflow() {
+    // 3 * x + 1 doesn't fit in a u64 on the first step
+    assert_eq!(next_value(18446744073709551615), None);
+    assert_eq!(next_value(6148914691236517205), None);
+    assert_eq!(next_value(6148914691236517204), Some(3074457345618258602));
+    assert_eq!(chain_length(18446744073709551615, length_cache(1000).as_slice()), 864);
+    assert_eq!(chain_length(18446744073709551613, length_cache(1000).as_slice()), 864);
+}
+
+#[test]
+fn test_chain_length() {
+    let cache = length_cache(10);
+    assert_eq!(chain_length(27, cache.as_slice()), 112);
+    assert_eq!(chain_length(1, cache.as_slice()), 1);
+    // 0 would go to 0 forever
+    assert_eq!(chain_length(0, cache.as_slice()), 0);
+    assert_eq!(chain_length(0, []), 0);
+}
+
+#[test]
+fn test_cache() {
+    let cache = length_cache(1000);
+    let expected: Vec<u32> = range(0u, 1000).map(|n| Hailstone::new(n).count() as u32).collect();
+    assert_eq!(cache, expected);
+}
+
+#[test]
+fn test_records() {
+    // http://oeis.org/A006877 and http://oeis.org/A008908
+    let expected = vec![(1u64, 1u64), (2, 2), (3, 8), (6, 9), (7, 17), (9, 20), (18, 21), (25, 24),
+        (27, 112), (54, 113), (73, 116), (97, 119), (129, 122), (171, 125), (231, 128),
+        (313, 131), (327, 144), (649, 145), (703, 171), (871, 179), (1161, 182), (2223, 183),
+        (2463, 209), (2919, 217), (3711, 238), (6171, 262), (10971, 268), (13255, 276),
+        (17647, 279), (23529, 282), (26623, 308), (34239, 311), (35655, 324), (52527, 340),
+        (77031, 351), (106239, 354), (142587, 375), (156159, 383), (216367, 386),
+        (230631, 443), (410011, 449), (511935, 470), (626331, 509), (837799, 525)];
+    // Big enough to use several ranges
+    assert_eq!(hailstone_records(1000000), expected);
+    assert_eq!(hailstone_records(3), expected.slice_to(2).to_vec());
+}
+
+#[test]
+fn test_statistics() {
+    let stats = CollatzMap::hailstone().statistics(27);
+    assert_eq!(stats, Statistics {
+        start: 27,
+        ending: ReachesOne,
+        steps: 111,
+        total_stopping_time: Some(111),
+        stopping_time: Some(96),
+        peak: 9232,
+        odd_steps: 41,
+        even_steps: 70
+    });
+
+    // Same as the length of the sequence
+    for n in range(1u, 1000) {
+        let stats = CollatzMap::hailstone().statistics(n as u64);
+        assert_eq!(stats.total_stopping_time, Some(Hailstone::new(n).count() - 1));
+        assert_eq!(stats.odd_steps + stats.even_steps, stats.steps);
+    }
+
+    let one = CollatzMap::hailstone().statistics(1);
+    assert_eq!(one.steps, 0);
+    assert_eq!(one.stopping_time, None);
+    assert_eq!(one.peak, 1);
+}
+
+#[test]
+fn test_cycles() {
+    let map = CollatzMap::new(5, 1);
+    let thirteen = vec![13, 66, 33, 166, 83, 416, 208, 104, 52, 26];
+
+    let stats = map.statistics(13);
+    assert_eq!(stats.ending, EntersCycle(thirteen.clone()));
+    assert_eq!(stats.steps, 10);
+    assert_eq!(stats.total_stopping_time, None);
+    assert_eq!(stats.peak, 416);
+
+    // 5 -> 26 -> 13
+    let stats = map.statistics(5);
+    assert_eq!(stats.ending, EntersCycle(thirteen));
+    assert_eq!(stats.steps, 11);
+
+    assert_eq!(map.statistics(17).ending,
+               EntersCycle(vec![17, 86, 43, 216, 108, 54, 27, 136, 68, 34]));
+    // 7 seems to grow forever
+    assert_eq!(map.statistics(7).ending, Overflows);
+    // 0 is a fixed point of every map
+    assert_eq!(map.statistics(0).ending, EntersCycle(vec![0]));
+    assert_eq!(CollatzMap::hailstone().statistics(18446744073709551615).ending, Overflows);
 }
@@ -1,9 +1,16 @@
 // Implements http://rosettacode.org/wiki/Hamming_numbers
+// and http://rosettacode.org/wiki/Humble_numbers
 // port of one of the scala solutions
 extern crate num;
+extern crate collections;
 use num::bigint::BigUint;
-use std::cmp::min;
-use std::sync::spsc_queue::Queue;
+use collections::{Deque, RingBuf};
+use std::num::pow;
+use prime_decomposition::is_prime;
+
+// only the primality test is used
+#[allow(dead_code)]
+mod prime_decomposition;
 
 // helper function to avoid repeating
 // FromPrimitive::from_int(i).unwrap()
@@ -14,106 +21,294 @@ fn int_to_biguint(i: int) -> BigUint {
 
 #[cfg(not(test))]
 fn main() {
-    let mut hamming = Hamming::new(1691);
+    let mut hamming = Hamming::new();
 
     println!("first 20 Hamming numbers")
     for _ in range(0,20) {
         print!("{} ", hamming.next().unwrap());
     }
 
+    // there's no need to go through all the numbers before
+    // these ones, we can compute them directly
     println!("\n\n1691st Hamming number");
-    // we've already iterated over the first 20 numbers
-    // so to get at number 1691 we have to subtract 20
-    println!("{}",hamming.nth(1691-20).unwrap())
+    println!("{}", nth_hamming(1691));
+
+    println!("\none millionth Hamming number");
+    println!("{}", nth_hamming(1000000));
+
+    println!("\nfirst 20 humble (7-smooth) numbers");
+    for n in SmoothNumbers::new([2, 3, 5, 7]).take(20) {
+        print!("{} ", n);
+    }
+    println!("");
 }
 
-// Hamming numbers are multiples
-// of 2, 3 or 5. We keep them on three
-// queues and extract the lowest (leftmost) value
-// from the three queues at each iteration
-struct Hamming {
-    q2: Queue<BigUint>,
-    q3: Queue<BigUint>,
-    q5: Queue<BigUint>
+// Smooth numbers are the numbers whose prime factors
+// all belong to a given set of primes. We keep one queue
+// per prime and extract the lowest (leftmost) value
+// from the queues at each iteration.
+// A number taken from the queue of the i-th prime is only
+// multiplied by the i-th and bigger primes, so every smooth
+// number is built in exactly one way (with its prime factors
+// in increasing order) and never shows up twice
+pub struct SmoothNumbers {
+    primes: Vec<BigUint>,
+    queues: Vec<RingBuf<BigUint>>,
+    // 1 is smooth for every set of primes, but it isn't
+    // in any of the queues
+    started: bool
 }
 
-impl Hamming {
+impl SmoothNumbers {
     // constructor method
-    // n initializes the capacity of the queues
-    fn new(n: uint) -> Hamming {
-        let h = Hamming {
-            q2: Queue::new(n),
-            q3: Queue::new(n),
-            q5: Queue::new(n)
-        };
-
-        h.q2.push(int_to_biguint(1));
-        h.q3.push(int_to_biguint(1));
-        h.q5.push(int_to_biguint(1));
-
-        h
+    // the queues grow as needed, so we don't have to
+    // know in advance how many numbers will be generated
+    pub fn new(primes: &[uint]) -> SmoothNumbers {
+        let mut primes: Vec<uint> = primes.to_vec();
+        primes.sort();
+        primes.dedup();
+        assert!(primes.len() > 0, "smooth numbers need at least one prime");
+        // with a composite number some smooth numbers would
+        // be built in more than one way, and come out twice
+        for &p in primes.iter() {
+            assert!(is_prime(p as u64), "{} isn't a prime", p);
+        }
+
+        let primes: Vec<BigUint> = primes.iter()
+            .map(|&p| FromPrimitive::from_uint(p).unwrap())
+            .collect();
+        let queues = Vec::from_fn(primes.len(), |_| RingBuf::new());
+
+        let mut s = SmoothNumbers { primes: primes, queues: queues, started: false };
+        s.enqueue(0, &int_to_biguint(1));
+        s
     }
 
-    // adds the next multiple (x2, x3, x5)
-    // to the queues
-    fn enqueue(&self, n: BigUint) {
-        self.q2.push(n * int_to_biguint(2));
-        self.q3.push(n * int_to_biguint(3));
-        self.q5.push(n * int_to_biguint(5));
+    // adds the multiples of n by the primes starting with
+    // the one at index first to their queues
+    fn enqueue(&mut self, first: uint, n: &BigUint) {
+        for i in range(first, self.primes.len()) {
+            let multiple = *n * *self.primes.get(i);
+            self.queues.get_mut(i).push_back(multiple);
+        }
     }
 }
 
 // implements an Iterator, so we
-// can extract Hamming numbers more easily
-impl Iterator<BigUint> for Hamming {
+// can extract smooth numbers more easily
+impl Iterator<BigUint> for SmoothNumbers {
     // the core of the work is done in the next method.
-    // We check which of the 3 queues has the lowest
-    // candidate and extract it as the next Hamming number
+    // We check which of the queues has the lowest
+    // candidate and extract it as the next smooth number
     fn next(&mut self) -> Option<BigUint> {
-        let (head2, head3, head5) =
-            (   self.q2.peek().unwrap(),
-                self.q3.peek().unwrap(),
-                self.q5.peek().unwrap());
-
-        let n = min(head2.clone(), min(head3.clone(), head5.clone()));
+        if !self.started {
+            self.started = true;
+            return Some(int_to_biguint(1));
+        }
 
-        if *head2 == n {self.q2.pop();}
-        if *head3 == n {self.q3.pop();}
-        if *head5 == n {self.q5.pop();}
+        // The queues are never empty, because every number we
+        // extract pushes at least its multiple by the last prime
+        let mut lowest = 0;
+        for i in range(1, self.queues.len()) {
+            if self.queues.get(i).front().unwrap() <
+               self.queues.get(lowest).front().unwrap() {
+                lowest = i;
+            }
+        }
 
-        self.enqueue(n.clone());
+        let n = self.queues.get_mut(lowest).pop_front().unwrap();
+        self.enqueue(lowest, &n);
         Some(n)
     }
 }
 
+// Hamming numbers are the 5-smooth numbers,
+// that is, multiples of 2, 3 and 5 only
+struct Hamming {
+    smooth: SmoothNumbers
+}
+
+impl Hamming {
+    fn new() -> Hamming {
+        Hamming { smooth: SmoothNumbers::new([2, 3, 5]) }
+    }
+}
+
+impl Iterator<BigUint> for Hamming {
+    fn next(&mut self) -> Option<BigUint> {
+        self.smooth.next()
+    }
+}
+
+// Returns the exponents (i, j, k) of the n-th Hamming number
+// 2^i * 3^j * 5^k (the first one being 1) without generating
+// any of the numbers before it.
+// In log2 space the Hamming numbers are the lattice points
+// (i, j, k) with value i + j * lg3 + k * lg5, and there are
+// about v^3 / (6 * lg3 * lg5) of them below the plane of value v.
+// That gives a good estimate of the value of the n-th one, so
+// we count the points below a thin band around the estimate
+// and then sort the few points inside the band.
+// Only j and k are looped over, the points for each (j, k) are counted
+// in closed form, so this takes O(n^(2/3)) time and O(n^(1/3)) memory
+pub fn nth_hamming_triple(n: u64) -> (uint, uint, uint) {
+    assert!(n > 0, "Hamming numbers are numbered from 1");
+    if n == 1 {
+        return (0, 0, 0);
+    }
+
+    let lg3 = 3f64.log2();
+    let lg5 = 5f64.log2();
+    // half the value of the (1, 1, 1) corner, which corrects
+    // the estimate for the points on the faces of the pyramid
+    let lg_sqrt30 = (1.0 + lg3 + lg5) / 2.0;
+
+    let nf = n as f64;
+    let estimate = (6.0 * lg3 * lg5 * nf).cbrt() - lg_sqrt30;
+    // numbers per unit of log2 around the estimate, used to pick
+    // a band that holds a few times the error of the estimate
+    let density = 3.0 * nf / (estimate + lg_sqrt30);
+    let width = (2.0 * estimate + 100.0) / density;
+
+    let mut hi = estimate + width / 2.0;
+    loop {
+        let lo = hi - width;
+        // number of points with value <= hi
+        let mut count = 0u64;
+        // points with lo < value <= hi
+        let mut band = vec![];
+
+        let mut k = 0u;
+        while (k as f64) * lg5 <= hi {
+            let mut j = 0u;
+            loop {
+                let base = (k as f64) * lg5 + (j as f64) * lg3;
+                if base > hi {
+                    break;
+                }
+
+                // every i up to last gives a point under the plane
+                let last = (hi - base).floor() as uint;
+                count += last as u64 + 1;
+
+                let first = (lo - base).floor() + 1.0;
+                let first = if first < 0.0 { 0 } else { first as uint };
+                for i in range(first, last + 1) {
+                    band.push((i as f64 + base, (i, j, k)));
+                }
+                j += 1;
+            }
+            k += 1;
+        }
+
+        // the estimate was off by more than we expected,
+        // so we move the band and try again
+        let below = count - band.len() as u64;
+        if count < n {
+            hi += width;
+            continue;
+        }
+        if below >= n {
+            hi -= width;
+            continue;
+        }
+
+        // sort the band from the largest to the smallest value
+        band.sort_by(|&(a, _), &(b, _)| {
+            if a > b { Less } else if a < b { Greater } else { Equal }
+        });
+        let (_, triple) = *band.get((count - n) as uint);
+        return triple;
+    }
+}
+
+// Returns the n-th Hamming number (the first one being 1)
+pub fn nth_hamming(n: u64) -> BigUint {
+    let (i, j, k) = nth_hamming_triple(n);
+    pow(int_to_biguint(2), i) * pow(int_to_biguint(3), j) * pow(int_to_biguint(5), k)
+}
+
 #[test]
 fn create() {
-    let h = Hamming::new(5);
-    h.q2.push(int_to_biguint(1));
-    h.q2.push(int_to_biguint(2));
-    h.q2.push(int_to_biguint(4));
+    let s = SmoothNumbers::new([5, 3, 2, 3]);
+    // the primes are sorted and deduplicated
+    assert!(s.primes == vec![int_to_biguint(2), int_to_biguint(3), int_to_biguint(5)]);
+    assert!(s.queues.iter().all(|q| q.len() == 1));
+    assert!(*s.queues.get(0).front().unwrap() == int_to_biguint(2));
+}
 
-    let _ = h.q2.peek();
-    assert!(h.q2.pop().unwrap() == int_to_biguint(1));
+#[test]
+#[should_fail]
+fn create_composite() {
+    SmoothNumbers::new([2, 4]);
 }
 
 #[test]
 fn try_enqueue() {
-    let h = Hamming::new(5);
-    h.enqueue(int_to_biguint(1));
-    h.enqueue(int_to_biguint(2));
-    h.enqueue(int_to_biguint(3));
-
-    assert!(h.q2.pop().unwrap() == int_to_biguint(1));
-    assert!(h.q3.pop().unwrap() == int_to_biguint(1));
-    assert!(h.q5.pop().unwrap() == int_to_biguint(1));
-    assert!(h.q2.pop().unwrap() == int_to_biguint(2));
-    assert!(h.q3.pop().unwrap() == int_to_biguint(3));
-    assert!(h.q5.pop().unwrap() == int_to_biguint(5));
- }
+    let mut s = SmoothNumbers::new([2, 3, 5]);
+    s.enqueue(0, &int_to_biguint(2));
+    s.enqueue(1, &int_to_biguint(3));
+    s.enqueue(2, &int_to_biguint(5));
+
+    // multiples of 2
+    assert!(s.queues.get_mut(0).pop_front().unwrap() == int_to_biguint(2));
+    assert!(s.queues.get_mut(0).pop_front().unwrap() == int_to_biguint(4));
+    assert!(s.queues.get(0).is_empty());
+    // multiples of 3
+    assert!(s.queues.get_mut(1).pop_front().unwrap() == int_to_biguint(3));
+    assert!(s.queues.get_mut(1).pop_front().unwrap() == int_to_biguint(6));
+    assert!(s.queues.get_mut(1).pop_front().unwrap() == int_to_biguint(9));
+    // multiples of 5
+    assert!(s.queues.get_mut(2).len() == 4);
+    assert!(s.queues.get_mut(2).pop_back().unwrap() == int_to_biguint(25));
+}
 
 #[test]
 fn hamming_iter() {
-    let mut hamming = Hamming::new(20);
+    let mut hamming = Hamming::new();
     assert!(hamming.nth(19).unwrap() == int_to_biguint(36));
-}
\ No newline at end of file
+}
+
+#[test]
+fn hamming_1691() {
+    let mut hamming = Hamming::new();
+    assert!(hamming.nth(1690).unwrap() == int_to_biguint(2125764000));
+}
+
+#[test]
+fn humble_numbers() {
+    let expected = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 12, 14, 15, 16, 18, 20, 21, 24, 25, 27];
+    let humble: Vec<BigUint> = SmoothNumbers::new([2, 3, 5, 7]).take(20).collect();
+    let expected: Vec<BigUint> = expected.iter().map(|&i| int_to_biguint(i)).collect();
+    assert!(humble == expected);
+}
+
+#[test]
+fn no_duplicates() {
+    let numbers: Vec<BigUint> = SmoothNumbers::new([2, 3, 5, 7, 11]).take(2000).collect();
+    assert!(numbers.as_slice().windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn direct_small() {
+    for (n, h) in Hamming::new().take(2000).enumerate() {
+        assert!(nth_hamming(n as u64 + 1) == h);
+    }
+}
+
+#[test]
+fn direct_large() {
+    let millionth: BigUint = from_str("519312780448388736089589843750000000000000000000000\
+                                       000000000000000000000000000000000").unwrap();
+    assert_eq!(nth_hamming_triple(1000000), (55, 47, 64));
+    assert!(nth_hamming(1000000) == millionth);
+    assert_eq!(nth_hamming_triple(1000000000), (1334, 335, 404));
+}
+
+// About 10^8 steps, too slow to run with the other tests
+#[test]
+#[ignore]
+fn direct_trillion() {
+    // Published on http://rosettacode.org/wiki/Hamming_numbers
+    assert_eq!(nth_hamming_triple(1000000000000), (1126, 16930, 40));
+}
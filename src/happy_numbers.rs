@@ -1,37 +1,181 @@
 // Implements http://rosettacode.org/wiki/Happy_numbers
 
-extern crate collections;
-use collections::treemap::TreeSet;
 #[cfg(not(test))]
 use std::iter::count;
+use std::num::pow;
+use std::iter::{range_inclusive, range_step};
+use cycles::collect_cycle;
 
-fn digits(mut n: uint) -> Vec<uint> {
+// We only need the cycle helpers from this module
+#[allow(dead_code)]
+mod cycles;
+
+fn digits(mut n: u64, base: u64) -> Vec<u64> {
     let mut ds = vec![];
     if n == 0 {
         return vec![0];
     }
     while n > 0 {
-        ds.push(n % 10);
-        n /= 10;
+        ds.push(n % base);
+        n /= base;
     }
     ds.reverse();
     ds
 }
 
-fn is_happy(mut x: uint) -> bool {
-    let mut past = TreeSet::new();
-    while x != 1 {
-        // Take the sum of the squares of the digits of x
-        x = digits(x).iter().fold(0, |a, &b| a + b * b);
+// The map taking a number to the sum of the powers of its digits in some
+// base. Base 10 and power 2 give the usual happy numbers
+// (http://oeis.org/A007770), the other ones give variants of them
+pub struct DigitPowerMap {
+    base: u64,
+    power: uint
+}
+
+// Where the iteration of the map goes from some number: the number of
+// steps before it enters a cycle, and the cycle itself
+#[deriving(PartialEq, Eq, Show)]
+pub struct Orbit {
+    pub tail: uint,
+    pub cycle: Vec<u64>
+}
+
+impl DigitPowerMap {
+    pub fn new(base: u64, power: uint) -> DigitPowerMap {
+        assert!(base >= 2);
+        DigitPowerMap { base: base, power: power }
+    }
+
+    pub fn apply(&self, n: u64) -> u64 {
+        digits(n, self.base).iter().fold(0, |a, &d| a + pow(d, self.power))
+    }
+
+    // Finds the orbit of n with Brent's cycle detection, so it doesn't
+    // need to remember the values it has seen
+    pub fn orbit(&self, n: u64) -> Orbit {
+        // Find the length of the cycle, moving the tortoise to the hare
+        // at every power of two
+        let mut power = 1u;
+        let mut length = 1u;
+        let mut tortoise = n;
+        let mut hare = self.apply(n);
+        while tortoise != hare {
+            if power == length {
+                tortoise = hare;
+                power *= 2;
+                length = 0;
+            }
+            hare = self.apply(hare);
+            length += 1;
+        }
+
+        // With the hare one cycle ahead, they meet where the cycle starts
+        let mut tail = 0;
+        tortoise = n;
+        hare = n;
+        for _ in range(0, length) {
+            hare = self.apply(hare);
+        }
+        while tortoise != hare {
+            tortoise = self.apply(tortoise);
+            hare = self.apply(hare);
+            tail += 1;
+        }
+
+        let cycle = collect_cycle(tortoise, length, |x| self.apply(x));
+        Orbit { tail: tail, cycle: cycle }
+    }
+
+    // The numbers which end at 1
+    pub fn is_happy(&self, n: u64) -> bool {
+        self.orbit(n).cycle == vec![1]
+    }
+
+    // The different cycles reached from the numbers below limit, in the
+    // order we find them
+    pub fn cycles(&self, limit: u64) -> Vec<Vec<u64>> {
+        let mut cycles: Vec<Vec<u64>> = vec![];
+        for n in range(1, limit) {
+            let cycle = self.orbit(n).cycle;
+            if !cycles.contains(&cycle) {
+                cycles.push(cycle);
+            }
+        }
+        cycles
+    }
+
+    // How many numbers in [1, bound) are happy. A number is happy if and
+    // only if the sum of the powers of its digits is, and that sum doesn't
+    // depend on the order of the digits. So for each prefix of the bound we
+    // enumerate the multisets of the remaining digits, and count their
+    // arrangements instead of the numbers themselves
+    pub fn count_happy_below(&self, bound: u64) -> u64 {
+        let ds = digits(bound, self.base);
+        let max_sum = ds.len() as u64 * pow(self.base - 1, self.power);
+        let happy: Vec<bool> = range_inclusive(0, max_sum).map(|s| self.is_happy(s)).collect();
+
+        // The numbers below the bound share its first i digits, then have
+        // a smaller digit, then any k digits
+        let mut count = 0;
+        let mut prefix_sum = 0;
+        for (i, &bound_digit) in ds.iter().enumerate() {
+            let k = ds.len() - i - 1;
+            if bound_digit > 0 {
+                let histogram = self.sums_histogram(k);
+                for d in range(0, bound_digit) {
+                    let start = prefix_sum + pow(d, self.power);
+                    for (s, &ways) in histogram.iter().enumerate() {
+                        if ways > 0 && *happy.get((start + s as u64) as uint) {
+                            count += ways;
+                        }
+                    }
+                }
+            }
+            prefix_sum += pow(bound_digit, self.power);
+        }
+        count
+    }
+
+    // The number of strings of k digits for every sum of the powers of
+    // their digits
+    fn sums_histogram(&self, k: uint) -> Vec<u64> {
+        let max_sum = k as u64 * pow(self.base - 1, self.power);
+        let mut histogram = Vec::from_elem(max_sum as uint + 1, 0u64);
+        self.add_multisets(0, k, 0, 1, binomials(k).as_slice(), &mut histogram);
+        histogram
+    }
 
-        // The number is not happy if there is an endless loop
-        if past.contains(&x) {
-            return false
+    // Chooses how many times each digit appears, from digit up, with
+    // remaining digits left to place. A multiset with c_0 zeros, c_1 ones...
+    // has C(k, c_0) * C(k - c_0, c_1) * ... arrangements
+    fn add_multisets(&self, digit: u64, remaining: uint, sum: u64, ways: u64,
+                     binomials: &[Vec<u64>], histogram: &mut Vec<u64>) {
+        let power = pow(digit, self.power);
+        if digit == self.base - 1 {
+            *histogram.get_mut((sum + remaining as u64 * power) as uint) += ways;
+            return;
+        }
+        for c in range_inclusive(0, remaining) {
+            self.add_multisets(digit + 1, remaining - c, sum + c as u64 * power,
+                               ways * *binomials[remaining].get(c), binomials, histogram);
         }
+    }
+}
 
-        past.insert(x);
+// Pascal's triangle up to row n
+fn binomials(n: uint) -> Vec<Vec<u64>> {
+    let mut rows: Vec<Vec<u64>> = vec![vec![1]];
+    for i in range_inclusive(1, n) {
+        let row = range_inclusive(0, i).map(|j| {
+            let prev = rows.get(i - 1);
+            (if j > 0 { *prev.get(j - 1) } else { 0 }) + (if j < i { *prev.get(j) } else { 0 })
+        }).collect();
+        rows.push(row);
     }
-    true
+    rows
+}
+
+fn is_happy(x: uint) -> bool {
+    DigitPowerMap::new(10, 2).is_happy(x as u64)
 }
 
 #[cfg(not(test))]
@@ -41,18 +185,28 @@ fn main() {
         .filter(|x| is_happy(*x))
         .take(8)
         .collect();
-    println!("{}", v)
+    println!("{}", v);
+
+    // The cycles of a few variants
+    for &(base, power) in [(10u64, 2u), (10, 3), (3, 2), (7, 2)].iter() {
+        let map = DigitPowerMap::new(base, power);
+        println!("Base {}, power {}: cycles {}", base, power, map.cycles(1000));
+    }
+
+    println!("There are {} happy numbers below 10^18",
+             DigitPowerMap::new(10, 2).count_happy_below(pow(10, 18)));
 }
 
 #[test]
 fn test_digits() {
-    assert_eq!(digits(0), vec![0]);
-    assert_eq!(digits(1), vec![1]);
-    assert_eq!(digits(2), vec![2]);
-    assert_eq!(digits(10), vec![1, 0]);
-    assert_eq!(digits(11), vec![1, 1]);
-    assert_eq!(digits(101), vec![1, 0, 1]);
-    assert_eq!(digits(1000), vec![1, 0, 0, 0]);
+    assert_eq!(digits(0, 10), vec![0]);
+    assert_eq!(digits(1, 10), vec![1]);
+    assert_eq!(digits(2, 10), vec![2]);
+    assert_eq!(digits(10, 10), vec![1, 0]);
+    assert_eq!(digits(11, 10), vec![1, 1]);
+    assert_eq!(digits(101, 10), vec![1, 0, 1]);
+    assert_eq!(digits(1000, 10), vec![1, 0, 0, 0]);
+    assert_eq!(digits(6, 2), vec![1, 1, 0]);
 }
 
 #[test]
@@ -63,3 +217,58 @@ fn test_is_happy() {
     assert!(happys.iter().all(|&n| is_happy(n)));
     assert!(unhappys.iter().all(|&n| !is_happy(n)));
 }
+
+#[test]
+fn test_orbit() {
+    let map = DigitPowerMap::new(10, 2);
+    // 2 -> 4 -> 16 -> 37 -> 58 -> 89 -> 145 -> 42 -> 20 -> 4
+    assert_eq!(map.orbit(2), Orbit { tail: 1, cycle: vec![4, 16, 37, 58, 89, 145, 42, 20] });
+    // 7 -> 49 -> 97 -> 130 -> 10 -> 1
+    assert_eq!(map.orbit(7), Orbit { tail: 5, cycle: vec![1] });
+    assert_eq!(map.orbit(1), Orbit { tail: 0, cycle: vec![1] });
+    assert_eq!(map.orbit(0), Orbit { tail: 0, cycle: vec![0] });
+}
+
+#[test]
+fn test_cycles() {
+    // Every number ends at 1 or in the cycle of 4
+    assert_eq!(DigitPowerMap::new(10, 2).cycles(1000),
+               vec![vec![1], vec![4, 16, 37, 58, 89, 145, 42, 20]]);
+
+    // The narcissistic numbers 153, 370, 371 and 407 are fixed points of
+    // the cubes, see http://oeis.org/A005188
+    let mut cubes = DigitPowerMap::new(10, 3).cycles(1000);
+    cubes.sort();
+    assert_eq!(cubes, vec![vec![1], vec![55, 250, 133], vec![136, 244], vec![153],
+                           vec![160, 217, 352], vec![370], vec![371], vec![407],
+                           vec![919, 1459]]);
+
+    // Every number is happy in base 2
+    assert_eq!(DigitPowerMap::new(2, 2).cycles(1000), vec![vec![1]]);
+    let mut base3 = DigitPowerMap::new(3, 2).cycles(1000);
+    base3.sort();
+    assert_eq!(base3, vec![vec![1], vec![2, 4], vec![5], vec![8]]);
+}
+
+#[test]
+fn test_count_happy_below() {
+    let map = DigitPowerMap::new(10, 2);
+
+    // Brute force up to 10^6
+    let limit = 1000000u64;
+    // below.get(n) is the number of happy numbers in [1, n)
+    let mut below = vec![0u64, 0];
+    for n in range(1, limit) {
+        let last = *below.last().unwrap();
+        below.push(last + if map.is_happy(n) { 1 } else { 0 });
+    }
+    for bound in range_inclusive(0, 1000u64).chain(range_step(1000, limit + 1, 997)) {
+        assert_eq!(map.count_happy_below(bound), *below.get(bound as uint));
+    }
+    for &bound in [10000u64, 100000, 123456, 654321, 999999, 1000000].iter() {
+        assert_eq!(map.count_happy_below(bound), *below.get(bound as uint));
+    }
+
+    // http://oeis.org/A068571 counts up to 10^n, and 10^n is happy
+    assert_eq!(map.count_happy_below(pow(10, 18)), 118226055080025490);
+}
@@ -0,0 +1,113 @@
+// Implements http://rosettacode.org/wiki/Sorting_algorithms/Heapsort
+#[cfg(test)]
+use std::cell::RefCell;
+
+// The sort only looks at the slice through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool;
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        v.swap(i, j);
+    }
+}
+
+struct Plain;
+
+impl<T: TotalOrd> Steps<T> for Plain {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        v[i] < v[j]
+    }
+}
+
+// Tells the caller of sort_watched about every comparison and swap
+struct Watched<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering,
+    compared: |uint, uint|: 'a,
+    swapped: |uint, uint|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        (self.compared)(i, j);
+        (self.compare)(&v[i], &v[j]) == Less
+    }
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        (self.swapped)(i, j);
+        v.swap(i, j);
+    }
+}
+
+fn heap_sort<T: TotalOrd>(v: &mut [T]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts v in the order given by compare, and calls compared and swapped
+// with the positions of every comparison and swap
+pub fn sort_watched<T>(v: &mut [T], compare: |&T, &T| -> Ordering,
+                       compared: |uint, uint|, swapped: |uint, uint|) {
+    let mut watched = Watched { compare: compare, compared: compared, swapped: swapped };
+    sort_steps(v, &mut watched);
+}
+
+fn sort_steps<T, S: Steps<T>>(v: &mut [T], steps: &mut S) {
+    let len = v.len();
+    // Build a max heap, from the last parent up
+    for start in range(0, len / 2).rev() {
+        sift_down(v, steps, start, len);
+    }
+    // Move the largest value to the end, and restore the heap before it
+    for end in range(1, len).rev() {
+        steps.swap(v, 0, end);
+        sift_down(v, steps, 0, end);
+    }
+}
+
+// Moves v[root] down the heap in v[..end] until its children are lower
+fn sift_down<T, S: Steps<T>>(v: &mut [T], steps: &mut S, root: uint, end: uint) {
+    let mut root = root;
+    loop {
+        let mut child = 2 * root + 1;
+        if child >= end {
+            return;
+        }
+        if child + 1 < end && steps.less(v, child, child + 1) {
+            child += 1;
+        }
+        if !steps.less(v, root, child) {
+            return;
+        }
+        steps.swap(v, root, child);
+        root = child;
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    heap_sort(numbers);
+    println!("{}", numbers.as_slice());
+}
+
+#[test]
+fn test_heap_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    heap_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
+
+    let mut empty: [int, ..0] = [];
+    heap_sort(empty);
+}
+
+#[test]
+fn test_sort_watched() {
+    let steps = RefCell::new(vec![]);
+    let mut numbers = [1i, 2];
+    sort_watched(numbers, |a, b| a.cmp(b),
+                 |i, j| steps.borrow_mut().push(("compare", i, j)),
+                 |i, j| steps.borrow_mut().push(("swap", i, j)));
+    assert_eq!(numbers.as_slice(), [1i, 2].as_slice());
+    // The heap puts 2 at the top, then swaps it to the end
+    assert_eq!(*steps.borrow(), vec![("compare", 0, 1), ("swap", 0, 1), ("swap", 0, 1)]);
+}
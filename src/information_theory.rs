@@ -0,0 +1,173 @@
+// Information theory measures built on top of http://rosettacode.org/wiki/Entropy
+// All the values are given in bits
+extern crate collections;
+
+use std::hash::Hash;
+use std::f64::INFINITY;
+use collections::hashmap::HashMap;
+use entropy::{entropy_from_counts, shannon_entropy};
+
+// We only need a couple of functions from the entropy module
+#[allow(dead_code)]
+mod entropy;
+
+// Counts the occurrences of each item and returns the entropy of the
+// resulting distribution
+fn entropy_of<T: Hash + Eq, I: Iterator<T>>(items: I) -> f64 {
+    let mut map = HashMap::<T, uint>::new();
+    let mut total = 0u;
+
+    for item in items {
+        map.insert_or_update_with(item, 1, |_, v| *v += 1);
+        total += 1;
+    }
+
+    entropy_from_counts(map.values().map(|&nb| nb), total)
+}
+
+// Entropy of the distribution of the n-grams (substrings of n chars) of a
+// string. For n = 1 this is the same as shannon_entropy
+pub fn ngram_entropy(s: &str, n: uint) -> f64 {
+    assert!(n > 0, "n-grams must contain at least one char");
+    let chars: Vec<char> = s.chars().collect();
+    entropy_of(chars.as_slice().windows(n))
+}
+
+// Order-k entropy: the entropy of a char given the k chars preceding it.
+// For k = 0 there is no context, so it is the first-order entropy
+pub fn order_k_entropy(s: &str, k: uint) -> f64 {
+    if k == 0 {
+        return shannon_entropy(s);
+    }
+
+    // Every (k + 1)-gram is a context followed by the char we try to predict
+    let chars: Vec<char> = s.chars().collect();
+    let windows: Vec<&[char]> = chars.as_slice().windows(k + 1).collect();
+    let contexts: Vec<&[char]> = windows.iter().map(|w| w.slice_to(k)).collect();
+    let nexts: Vec<char> = windows.iter().map(|w| w[k]).collect();
+
+    conditional_entropy(nexts.as_slice(), contexts.as_slice())
+}
+
+// Joint entropy H(X, Y) of two paired sequences
+pub fn joint_entropy<T: Hash + Eq, U: Hash + Eq>(xs: &[T], ys: &[U]) -> f64 {
+    assert!(xs.len() == ys.len(), "the sequences must have the same length");
+    entropy_of(xs.iter().zip(ys.iter()))
+}
+
+// Conditional entropy H(X | Y): the uncertainty left about X once Y is known
+pub fn conditional_entropy<T: Hash + Eq, U: Hash + Eq>(xs: &[T], ys: &[U]) -> f64 {
+    joint_entropy(xs, ys) - entropy_of(ys.iter())
+}
+
+// Mutual information I(X; Y): how much knowing one of the sequences tells
+// us about the other one
+pub fn mutual_information<T: Hash + Eq, U: Hash + Eq>(xs: &[T], ys: &[U]) -> f64 {
+    entropy_of(xs.iter()) + entropy_of(ys.iter()) - joint_entropy(xs, ys)
+}
+
+// Kullback-Leibler divergence D(P || Q) between two distributions given as
+// frequencies of the same symbols. It is infinite if Q gives probability zero
+// to a symbol that P can produce
+pub fn kl_divergence(p: &[uint], q: &[uint]) -> f64 {
+    assert!(p.len() == q.len(), "the distributions must have the same length");
+
+    let p_total = p.iter().fold(0u, |acc, &x| acc + x) as f64;
+    let q_total = q.iter().fold(0u, |acc, &x| acc + x) as f64;
+
+    p.iter().zip(q.iter()).filter(|&(&px, _)| px > 0).fold(0f64, |acc, (&px, &qx)| {
+        if qx == 0 {
+            return INFINITY;
+        }
+        let pp = (px as f64) / p_total;
+        let qp = (qx as f64) / q_total;
+        acc + pp * (pp / qp).log2()
+    })
+}
+
+#[cfg(not(test))]
+fn main() {
+    let s = "Rosetta Code";
+    for k in range(0u, 4) {
+        println!("order-{} entropy of {}: {:f}", k, s, order_k_entropy(s, k));
+    }
+
+    let xs = [0u, 0, 1, 1, 2, 2, 3, 3];
+    let ys = [0u, 0, 1, 1, 0, 0, 1, 1];
+    println!("H(X, Y) = {:f}", joint_entropy(xs, ys));
+    println!("H(X | Y) = {:f}", conditional_entropy(xs, ys));
+    println!("I(X; Y) = {:f}", mutual_information(xs, ys));
+    println!("D(P || Q) = {:f}", kl_divergence([1, 1], [1, 3]));
+}
+
+#[cfg(test)]
+static EPSILON: f64 = 0.0000001;
+
+// Builds the paired sequences of the joint distribution of example 2.2.1
+// of Cover and Thomas' "Elements of Information Theory". Each count is the
+// probability of the pair multiplied by 32
+#[cfg(test)]
+fn cover_thomas_example() -> (Vec<uint>, Vec<uint>) {
+    let table = [[4u, 2, 1, 1],
+                 [2, 4, 1, 1],
+                 [2, 2, 2, 2],
+                 [8, 0, 0, 0]];
+    let mut xs = vec![];
+    let mut ys = vec![];
+    for (y, row) in table.iter().enumerate() {
+        for (x, &count) in row.iter().enumerate() {
+            for _ in range(0, count) {
+                xs.push(x);
+                ys.push(y);
+            }
+        }
+    }
+    (xs, ys)
+}
+
+#[test]
+fn test_joint_and_conditional() {
+    let (xs, ys) = cover_thomas_example();
+    let (xs, ys) = (xs.as_slice(), ys.as_slice());
+
+    assert!((joint_entropy(xs, ys) - 27.0 / 8.0).abs() < EPSILON);
+    assert!((conditional_entropy(xs, ys) - 11.0 / 8.0).abs() < EPSILON);
+    assert!((conditional_entropy(ys, xs) - 13.0 / 8.0).abs() < EPSILON);
+    assert!((mutual_information(xs, ys) - 3.0 / 8.0).abs() < EPSILON);
+    assert!((mutual_information(ys, xs) - 3.0 / 8.0).abs() < EPSILON);
+}
+
+#[test]
+fn test_independent_and_identical() {
+    let xs = [0u, 0, 1, 1];
+    let ys = [0u, 1, 0, 1];
+    assert!((joint_entropy(xs, ys) - 2.0).abs() < EPSILON);
+    assert!(mutual_information(xs, ys).abs() < EPSILON);
+
+    assert!((mutual_information(xs, xs) - 1.0).abs() < EPSILON);
+    assert!(conditional_entropy(xs, xs).abs() < EPSILON);
+}
+
+#[test]
+fn test_ngrams() {
+    // Same as the first-order entropy
+    assert!((ngram_entropy("1223334444", 1) - 1.846439344671).abs() < EPSILON);
+    assert!((order_k_entropy("1223334444", 0) - 1.846439344671).abs() < EPSILON);
+
+    // "ab", "ba", "ab", "ba", "ab"
+    assert!((ngram_entropy("ababab", 2) - 0.970950594455).abs() < EPSILON);
+
+    // In a periodic string every char is determined by the ones preceding it
+    assert!((order_k_entropy("0101010101", 0) - 1.0).abs() < EPSILON);
+    assert!(order_k_entropy("0101010101", 1).abs() < EPSILON);
+    assert!(order_k_entropy("011011011011", 2).abs() < EPSILON);
+}
+
+#[test]
+fn test_kl_divergence() {
+    assert!((kl_divergence([1, 1], [1, 3]) - 0.207518749639).abs() < EPSILON);
+    assert!((kl_divergence([1, 3], [1, 1]) - 0.188721875541).abs() < EPSILON);
+    assert!(kl_divergence([2, 5, 3], [4, 10, 6]).abs() < EPSILON);
+    assert_eq!(kl_divergence([1, 1], [1, 0]), INFINITY);
+    assert!(kl_divergence([1, 0], [1, 1]) == 1.0);
+}
@@ -0,0 +1,93 @@
+// Implements http://rosettacode.org/wiki/Sorting_algorithms/Insertion_sort
+#[cfg(test)]
+use std::cell::RefCell;
+
+// The sort only looks at the slice through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool;
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        v.swap(i, j);
+    }
+}
+
+struct Plain;
+
+impl<T: TotalOrd> Steps<T> for Plain {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        v[i] < v[j]
+    }
+}
+
+// Tells the caller of sort_watched about every comparison and swap
+struct Watched<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering,
+    compared: |uint, uint|: 'a,
+    swapped: |uint, uint|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn less(&mut self, v: &[T], i: uint, j: uint) -> bool {
+        (self.compared)(i, j);
+        (self.compare)(&v[i], &v[j]) == Less
+    }
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        (self.swapped)(i, j);
+        v.swap(i, j);
+    }
+}
+
+fn insertion_sort<T: TotalOrd>(v: &mut [T]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts v in the order given by compare, and calls compared and swapped
+// with the positions of every comparison and swap
+pub fn sort_watched<T>(v: &mut [T], compare: |&T, &T| -> Ordering,
+                       compared: |uint, uint|, swapped: |uint, uint|) {
+    let mut watched = Watched { compare: compare, compared: compared, swapped: swapped };
+    sort_steps(v, &mut watched);
+}
+
+// Every value is moved to the left, past the bigger ones, until the
+// beginning of the slice is sorted up to it
+fn sort_steps<T, S: Steps<T>>(v: &mut [T], steps: &mut S) {
+    for i in range(1, v.len()) {
+        let mut j = i;
+        while j > 0 && steps.less(v, j, j - 1) {
+            steps.swap(v, j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    insertion_sort(numbers);
+    println!("{}", numbers.as_slice());
+}
+
+#[test]
+fn test_insertion_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    insertion_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
+
+    let mut empty: [int, ..0] = [];
+    insertion_sort(empty);
+}
+
+#[test]
+fn test_sort_watched() {
+    let steps = RefCell::new(vec![]);
+    let mut numbers = [3i, 1, 2];
+    sort_watched(numbers, |a, b| a.cmp(b),
+                 |i, j| steps.borrow_mut().push(("compare", i, j)),
+                 |i, j| steps.borrow_mut().push(("swap", i, j)));
+    assert_eq!(numbers.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!(*steps.borrow(), vec![("compare", 1, 0), ("swap", 1, 0), ("compare", 2, 1),
+                                     ("swap", 2, 1), ("compare", 1, 0)]);
+}
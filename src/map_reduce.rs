@@ -0,0 +1,225 @@
+// Parallel map-reduce over a fixed pool of worker tasks
+// Used by parallel_calculations (http://rosettacode.org/wiki/Parallel_calculations)
+// See http://static.rust-lang.org/doc/master/guide-tasks.html for information
+// about tasks and channels
+extern crate sync;
+extern crate collections;
+
+use std::cmp::max;
+use std::os::num_cpus;
+use sync::{Arc, Mutex};
+use collections::HashMap;
+
+// Returned when the map function failed on some of the inputs. The failing
+// worker dies, so we can't know which input was to blame, only which chunks
+// never came back
+#[deriving(Show, PartialEq)]
+pub struct TaskFailure {
+    pub failed_chunks: Vec<uint>
+}
+
+// Splits the inputs into chunks of chunk_size elements and sends them to
+// a pool of workers, so we never have more than "workers" tasks no matter
+// how many inputs there are. The workers take a new chunk from a shared
+// queue as soon as they are done with the previous one
+pub struct MapReduce {
+    workers: uint,
+    chunk_size: uint,
+    ordered: bool
+}
+
+impl MapReduce {
+    // By default there is one worker per CPU, the chunks have 1024 elements
+    // and the results are reduced in the same order as the inputs
+    pub fn new() -> MapReduce {
+        MapReduce { workers: max(num_cpus(), 1), chunk_size: 1024, ordered: true }
+    }
+
+    pub fn workers(self, workers: uint) -> MapReduce {
+        assert!(workers > 0, "we need at least one worker");
+        MapReduce { workers: workers, ..self }
+    }
+
+    pub fn chunk_size(self, chunk_size: uint) -> MapReduce {
+        assert!(chunk_size > 0, "chunks can't be empty");
+        MapReduce { chunk_size: chunk_size, ..self }
+    }
+
+    // If the results don't need to be reduced in order, each chunk is
+    // reduced as soon as it arrives instead of waiting for the previous ones
+    pub fn ordered(self, ordered: bool) -> MapReduce {
+        MapReduce { ordered: ordered, ..self }
+    }
+
+    // Applies map to every input in the workers, and folds the results with
+    // reduce, starting from init, in the task that called run
+    pub fn run<T: Send, U: Send, A>(&self, inputs: Vec<T>, map: fn(T) -> U,
+                                    init: A, reduce: |A, U| -> A) -> Result<A, TaskFailure> {
+        // Queue all the chunks. Once the queue is empty the workers stop
+        let (job_sender, job_receiver) = channel();
+        let mut chunks = 0u;
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        for x in inputs.move_iter() {
+            chunk.push(x);
+            if chunk.len() == self.chunk_size {
+                job_sender.send((chunks, chunk));
+                chunks += 1;
+                chunk = Vec::with_capacity(self.chunk_size);
+            }
+        }
+        if !chunk.is_empty() {
+            job_sender.send((chunks, chunk));
+            chunks += 1;
+        }
+        drop(job_sender);
+
+        // The receiving end of a channel can't be shared, so the workers
+        // take turns with it
+        let jobs = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, result_receiver) = channel();
+        for _ in range(0, self.workers) {
+            let jobs = jobs.clone();
+            let results = result_sender.clone();
+            spawn(proc() {
+                loop {
+                    // The lock is released before we start working
+                    let job = jobs.lock().recv_opt();
+                    match job {
+                        Ok((index, chunk)) => {
+                            let mapped: Vec<U> = chunk.move_iter().map(|x| map(x)).collect();
+                            results.send((index, mapped));
+                        }
+                        Err(()) => break
+                    }
+                }
+            });
+        }
+        // Only the workers can send results now, so when all of them are
+        // gone (whether they finished or failed) recv_opt stops waiting
+        drop(result_sender);
+
+        let mut acc = init;
+        let mut received = Vec::from_elem(chunks, false);
+        let mut received_count = 0;
+        // Chunks which arrived before their turn, when reducing in order
+        let mut pending = HashMap::new();
+        let mut next = 0u;
+
+        while received_count < chunks {
+            let (index, mapped) = match result_receiver.recv_opt() {
+                Ok(result) => result,
+                Err(()) => {
+                    let failed = range(0, chunks).filter(|&i| !*received.get(i)).collect();
+                    return Err(TaskFailure { failed_chunks: failed });
+                }
+            };
+            *received.get_mut(index) = true;
+            received_count += 1;
+
+            if !self.ordered {
+                for y in mapped.move_iter() {
+                    acc = reduce(acc, y);
+                }
+                continue;
+            }
+
+            pending.insert(index, mapped);
+            loop {
+                match pending.pop(&next) {
+                    Some(mapped) => {
+                        for y in mapped.move_iter() {
+                            acc = reduce(acc, y);
+                        }
+                        next += 1;
+                    }
+                    None => break
+                }
+            }
+        }
+
+        Ok(acc)
+    }
+
+    // Applies map to every input in the workers and collects the results
+    pub fn map<T: Send, U: Send>(&self, inputs: Vec<T>, map: fn(T) -> U)
+                                 -> Result<Vec<U>, TaskFailure> {
+        let len = inputs.len();
+        self.run(inputs, map, Vec::with_capacity(len), |mut results, y| {
+            results.push(y);
+            results
+        })
+    }
+}
+
+#[cfg(not(test))]
+fn square(x: u64) -> u64 {
+    x * x
+}
+
+// Needed so parallel_calculations compiles cleanly, because it
+// uses this code as a library
+#[allow(dead_code)]
+#[cfg(not(test))]
+fn main() {
+    let numbers = range(1u64, 1000001).collect();
+    let sum = MapReduce::new().chunk_size(10000).ordered(false)
+                              .run(numbers, square, 0u64, |acc, x| acc + x)
+                              .unwrap();
+    println!("The sum of the squares from 1 to 1000000 is {}", sum);
+}
+
+#[cfg(test)]
+fn double(x: uint) -> uint {
+    x * 2
+}
+
+#[cfg(test)]
+fn fail_on_seven(x: uint) -> uint {
+    if x == 7 {
+        fail!("seven is not allowed");
+    }
+    x
+}
+
+#[test]
+fn test_ordered() {
+    let numbers: Vec<uint> = range(0u, 1000).collect();
+    let expected: Vec<uint> = numbers.iter().map(|&x| x * 2).collect();
+
+    for &workers in [1u, 2, 7].iter() {
+        for &chunk_size in [1u, 3, 1000, 5000].iter() {
+            let mr = MapReduce::new().workers(workers).chunk_size(chunk_size);
+            assert_eq!(mr.map(numbers.clone(), double), Ok(expected.clone()));
+        }
+    }
+}
+
+#[test]
+fn test_unordered() {
+    let numbers: Vec<uint> = range(0u, 1000).collect();
+    let mr = MapReduce::new().workers(4).chunk_size(10).ordered(false);
+
+    let sum = mr.run(numbers.clone(), double, 0u, |acc, x| acc + x);
+    assert_eq!(sum, Ok(999 * 1000));
+
+    let expected: Vec<uint> = range(0u, 1000).map(|x| x * 2).collect();
+    let mut doubled = mr.map(numbers, double).unwrap();
+    doubled.sort();
+    assert_eq!(doubled, expected);
+}
+
+#[test]
+fn test_empty() {
+    let mr = MapReduce::new();
+    assert_eq!(mr.map(vec![], double), Ok(vec![]));
+    assert_eq!(mr.run(vec![], double, 42u, |acc, x| acc + x), Ok(42));
+}
+
+#[test]
+fn test_failure() {
+    let numbers: Vec<uint> = range(0u, 20).collect();
+    let mr = MapReduce::new().workers(3).chunk_size(5);
+    // 7 is in the second chunk
+    assert_eq!(mr.map(numbers, fail_on_seven),
+               Err(TaskFailure { failed_chunks: vec![1] }));
+}
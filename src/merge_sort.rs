@@ -0,0 +1,129 @@
+// Implements http://rosettacode.org/wiki/Sorting_algorithms/Merge_sort
+use std::cmp::min;
+#[cfg(test)]
+use std::cell::RefCell;
+
+// The sort only looks at the slice through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    // Whether copy[i] goes after copy[j]
+    fn greater(&mut self, copy: &[T], i: uint, j: uint) -> bool;
+
+    fn write(&mut self, v: &mut [T], i: uint, value: T) {
+        v[i] = value;
+    }
+}
+
+struct Plain;
+
+impl<T: TotalOrd> Steps<T> for Plain {
+    fn greater(&mut self, copy: &[T], i: uint, j: uint) -> bool {
+        copy[i] > copy[j]
+    }
+}
+
+// Tells the caller of sort_watched about every comparison and write
+struct Watched<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering,
+    compared: |uint, uint|: 'a,
+    written: |uint, &T|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn greater(&mut self, copy: &[T], i: uint, j: uint) -> bool {
+        (self.compared)(i, j);
+        (self.compare)(&copy[i], &copy[j]) == Greater
+    }
+
+    fn write(&mut self, v: &mut [T], i: uint, value: T) {
+        (self.written)(i, &value);
+        v[i] = value;
+    }
+}
+
+fn merge_sort<T: TotalOrd + Clone>(v: &mut [T]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts v in the order given by compare. compared is called with the
+// positions of every comparison, in the copy of v the pass reads from, and
+// written with every value written to v
+pub fn sort_watched<T: Clone>(v: &mut [T], compare: |&T, &T| -> Ordering,
+                              compared: |uint, uint|, written: |uint, &T|) {
+    let mut watched = Watched { compare: compare, compared: compared, written: written };
+    sort_steps(v, &mut watched);
+}
+
+// Bottom up merge sort: merges the runs of length 1, then those of length
+// 2, 4... Each pass reads from a copy of the slice
+fn sort_steps<T: Clone, S: Steps<T>>(v: &mut [T], steps: &mut S) {
+    let len = v.len();
+    let mut width = 1;
+    while width < len {
+        let copy = v.to_vec();
+        let mut lo = 0;
+        while lo < len {
+            let mid = min(lo + width, len);
+            let hi = min(lo + 2 * width, len);
+            // A run without a partner is already in place
+            if mid < hi {
+                merge(v, steps, copy.as_slice(), lo, mid, hi);
+            }
+            lo = hi;
+        }
+        width *= 2;
+    }
+}
+
+// Merges the sorted runs copy[lo..mid] and copy[mid..hi] into v[lo..hi]
+fn merge<T: Clone, S: Steps<T>>(v: &mut [T], steps: &mut S, copy: &[T],
+                                lo: uint, mid: uint, hi: uint) {
+    let mut i = lo;
+    let mut j = mid;
+    for k in range(lo, hi) {
+        // Taking from the left run when the values are equal keeps the
+        // sort stable
+        let take_left = j >= hi || (i < mid && !steps.greater(copy, i, j));
+        if take_left {
+            steps.write(v, k, copy[i].clone());
+            i += 1;
+        } else {
+            steps.write(v, k, copy[j].clone());
+            j += 1;
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    merge_sort(numbers);
+    println!("{}", numbers.as_slice());
+
+    let mut strings = ["beach", "hotel", "airplane", "car", "house", "art"];
+    merge_sort(strings);
+    println!("{}", strings.as_slice());
+}
+
+#[test]
+fn test_merge_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    merge_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
+
+    let mut strings = ["beach", "hotel", "airplane", "car", "house", "art"];
+    merge_sort(strings);
+    assert_eq!(strings.as_slice(),
+               ["airplane", "art", "beach", "car", "hotel", "house"].as_slice());
+}
+
+#[test]
+fn test_sort_watched() {
+    let steps = RefCell::new(vec![]);
+    let mut numbers = [2i, 1];
+    sort_watched(numbers, |a, b| a.cmp(b),
+                 |i, j| steps.borrow_mut().push(("compare", i, j)),
+                 |i, &x| steps.borrow_mut().push(("write", i, x as uint)));
+    assert_eq!(numbers.as_slice(), [1i, 2].as_slice());
+    assert_eq!(*steps.borrow(), vec![("compare", 0, 1), ("write", 0, 1), ("write", 1, 2)]);
+}
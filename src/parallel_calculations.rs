@@ -2,14 +2,19 @@
 // See http://static.rust-lang.org/doc/master/guide-tasks.html for information
 // about tasks, channels, future, etc.
 
-#[cfg(test)]
-extern crate test;
 extern crate sync;
+extern crate collections;
+extern crate num;
 
-use sync::Future;
+use map_reduce::{MapReduce, TaskFailure};
 use prime_decomposition::factor;
 
+// We only need factor from these modules, and not every option of the
+// map-reduce
+#[allow(dead_code)]
 mod prime_decomposition;
+#[allow(dead_code)]
+mod map_reduce;
 
 // Returns the minimal prime factor of a number
 fn min_factor(x: uint) -> uint {
@@ -18,34 +23,14 @@ fn min_factor(x: uint) -> uint {
 }
 
 // Returns the largest minimal factor of the numbers in a slice
-// The function is implemented using a channel
-#[cfg(test)]
-fn largest_min_factor_chan(numbers: &[uint]) -> uint {
-    let (sender, receiver) = channel();
-
-    // Send all the minimal factors
-    for &x in numbers.iter() {
-        let child_sender = sender.clone();
-        spawn(proc() { child_sender.send(min_factor(x)) });
-    }
-
-    // Receive them and keep the largest one
-    numbers.iter().fold(0u, |max, _| {
-        std::cmp::max(receiver.recv(), max)
-    })
-}
-
-// Returns the largest minimal factor of the numbers in a slice
-// The function is implemented using the Future struct
-fn largest_min_factor_fut(numbers: &[uint]) -> uint {
-    // We will save the future values of the minimal factor in the results vec
-    let mut results = Vec::from_fn(numbers.len(), |i| {
-        let number = numbers[i];
-        Future::spawn(proc() { min_factor(number) })
-    });
-
-    // Get the largest minimal factor of all results
-    results.mut_iter().map(|r| r.get()).max().unwrap()
+// The numbers are split in chunks and factorized by a fixed pool of worker
+// tasks, so we don't spawn a task per number. If the factorization fails
+// for some of the numbers, we get back the chunks which failed
+fn largest_min_factor(numbers: &[uint]) -> Result<uint, TaskFailure> {
+    // Every number is a lot of work, so we use small chunks
+    MapReduce::new().chunk_size(16)
+                    .ordered(false)
+                    .run(numbers.to_vec(), min_factor, 0u, |max, x| std::cmp::max(max, x))
 }
 
 #[cfg(not(test))]
@@ -58,7 +43,7 @@ fn main() {
                    1157978,
                    1099726];
 
-    let max = largest_min_factor_fut(numbers);
+    let max = largest_min_factor(numbers).unwrap();
     println!("The largest minimal factor is {}", max);
 }
 
@@ -66,7 +51,7 @@ fn main() {
 #[test]
 fn test_basic() {
     let numbers = &[25, 80, 256, 55, 18, 19];
-    assert_eq!(largest_min_factor_fut(numbers), 19);
+    assert_eq!(largest_min_factor(numbers), Ok(19));
 }
 
 #[test]
@@ -77,6 +62,21 @@ fn test_equivalence() {
                    1152800,
                    1157978,
                    1099726];
-    assert_eq!(largest_min_factor_chan(numbers),
-                largest_min_factor_fut(numbers));
+    let sequential = numbers.iter().map(|&x| min_factor(x)).max().unwrap();
+    assert_eq!(largest_min_factor(numbers), Ok(sequential));
+}
+
+#[test]
+fn test_many_numbers() {
+    // Far more numbers than we would want tasks
+    let numbers: Vec<uint> = range(2u, 100000).collect();
+    let sequential = numbers.iter().map(|&x| min_factor(x)).max().unwrap();
+    assert_eq!(largest_min_factor(numbers.as_slice()), Ok(sequential));
+}
+
+#[test]
+fn test_failure() {
+    // 1 has no prime factors, so min_factor fails
+    let numbers = &[25, 80, 1, 55];
+    assert!(largest_min_factor(numbers).is_err());
 }
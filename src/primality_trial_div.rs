@@ -1,27 +1,50 @@
 //Implements http://rosettacode.org/wiki/Primality_by_Trial_Division
+// and http://rosettacode.org/wiki/Miller-Rabin_primality_test
+extern crate num;
 
-use std::iter::range_step;
+use std::iter::range_step_inclusive;
+use sieve_eratosthenes::int_sqrt_u64;
+#[cfg(not(test))]
+use num::bigint::BigUint;
+#[cfg(not(test))]
+use prime_decomposition::is_prime_big;
+
+// The Miller-Rabin and Baillie-PSW tests are in prime_decomposition, which
+// uses them to factorize
+#[allow(dead_code)]
+mod prime_decomposition;
+// We only need the integer square root from this module
+#[allow(dead_code)]
+mod sieve_eratosthenes;
 
 fn is_prime(number: int) -> bool {
-    if number % 2 == 0 && number != 2 {
+    // 0, 1 and the negative numbers aren't prime
+    if number < 2 {
         return false;
     }
+    if number % 2 == 0 {
+        return number == 2;
+    }
 
-    let limit = (number as f32).sqrt() as int + 1;
+    let limit = int_sqrt_u64(number as u64) as int;
 
     // We test if the number is divisible by any odd number up to the limit
-    range_step(3, limit, 2).all(|x| number % x != 0)
+    range_step_inclusive(3, limit, 2).all(|x| number % x != 0)
 }
 
 #[cfg(not(test))]
 fn main() {
     println!("{:b}", is_prime(15485863)); // The 1 000 000th prime.
     println!("{:b}", is_prime(62773913)); // The product of the 1000th and 1001st primes.
+    println!("{:b}", prime_decomposition::is_prime(18446744073709551557)); // The largest u64 prime.
+
+    let mersenne: BigUint = from_str("170141183460469231731687303715884105727").unwrap();
+    println!("{:b}", is_prime_big(&mersenne)); // 2^127 - 1
 }
 
 #[test]
 fn test_one() {
-    assert!(is_prime(1));
+    assert!(!is_prime(1));
 }
 
 #[test]
@@ -33,4 +56,41 @@ fn test_two() {
 fn test_many() {
     let primes = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
     assert!(primes.iter().all(|&x| is_prime(x)));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_not_prime() {
+    let not_primes = [-7, -2, -1, 0, 1, 4, 9, 15, 25, 49, 62773913];
+    assert!(not_primes.iter().all(|&x| !is_prime(x)));
+
+    // The square of 33554473. Its square root computed with f32 is too small,
+    // so the old version never tried to divide by 33554473
+    assert!(!is_prime(1125902658307729));
+}
+
+#[test]
+fn test_isqrt() {
+    assert_eq!(int_sqrt_u64(0), 0);
+    assert_eq!(int_sqrt_u64(15), 3);
+    assert_eq!(int_sqrt_u64(16), 4);
+    assert_eq!(int_sqrt_u64(1125902658307729), 33554473);
+    assert_eq!(int_sqrt_u64(1125902658307728), 33554472);
+    assert_eq!(int_sqrt_u64(18446744073709551615), 4294967295);
+}
+
+#[test]
+fn test_miller_rabin() {
+    use prime_decomposition::is_prime;
+
+    // Same results as trial division for small numbers
+    for n in range(0u64, 10000) {
+        assert_eq!(is_prime(n), self::is_prime(n as int));
+    }
+
+    assert!(is_prime(4294967291));
+    assert!(is_prime(18446744073709551557));
+    // Strong pseudoprimes to several prime bases
+    assert!(!is_prime(3215031751));
+    assert!(!is_prime(3825123056546413051));
+    assert!(!is_prime(18446744030759878681)); // 4294967291^2
+}
@@ -0,0 +1,95 @@
+// Implements http://rosettacode.org/wiki/Legendre_prime_counting_function
+// with Lucy_Hedgehog's variant of the Legendre / Meissel-Lehmer method
+
+use sieve_eratosthenes::{int_sqrt_u64, primes_in_range};
+
+// We only need a couple of functions from the sieve
+#[allow(dead_code)]
+mod sieve_eratosthenes;
+
+// Returns pi(x), the number of primes less than or equal to x.
+// Let S(v, p) be the number of integers in [2, v] which are either prime or
+// have no prime factor up to p. S(v, 1) = v - 1 and S(v, sqrt(v)) = pi(v).
+// Going from p - 1 to a prime p removes the numbers whose smallest prime
+// factor is p:
+//     S(v, p) = S(v, p - 1) - (S(v / p, p - 1) - S(p - 1, p - 1))
+// We only ever need S for the values x / i, of which there are about
+// 2 * sqrt(x), so it takes O(x^(3/4)) time and O(x^(1/2)) memory
+pub fn prime_count(x: u64) -> u64 {
+    if x < 2 {
+        return 0;
+    }
+
+    let r = int_sqrt_u64(x);
+    // small.get(v) is S(v) for v <= r, large.get(i) is S(x / i) for i <= r
+    let mut small: Vec<u64> = range(0, r + 1).map(|v| if v == 0 { 0 } else { v - 1 }).collect();
+    let mut large: Vec<u64> = range(0, r + 1).map(|i| if i == 0 { 0 } else { x / i - 1 })
+                                             .collect();
+
+    for p in primes_in_range(2, r + 1).move_iter() {
+        // The primes below p
+        let sp = *small.get((p - 1) as uint);
+        let p2 = p * p;
+
+        // S(x / i) for every i with x / i >= p^2. S(x / i / p) is either a
+        // large value, or a small one if i * p is past the square root
+        let last = if x / p2 < r { x / p2 } else { r };
+        for i in range(1, last + 1) {
+            let d = i * p;
+            let s = if d <= r {
+                *large.get(d as uint)
+            } else {
+                *small.get((x / d) as uint)
+            };
+            *large.get_mut(i as uint) -= s - sp;
+        }
+
+        // S(v) for small v >= p^2. We go down so that S(v / p) still has
+        // its value for p - 1
+        let mut v = r;
+        while v >= p2 {
+            let s = *small.get((v / p) as uint);
+            *small.get_mut(v as uint) -= s - sp;
+            v -= 1;
+        }
+    }
+
+    *large.get(1)
+}
+
+#[cfg(not(test))]
+fn main() {
+    let mut x = 1u64;
+    for k in range(0u, 13) {
+        println!("pi(10^{}) = {}", k, prime_count(x));
+        x *= 10;
+    }
+}
+
+#[test]
+fn test_small() {
+    assert_eq!(prime_count(0), 0);
+    assert_eq!(prime_count(1), 0);
+    assert_eq!(prime_count(2), 1);
+    assert_eq!(prime_count(3), 2);
+    assert_eq!(prime_count(4), 2);
+
+    // Same as counting the primes of the sieve
+    let primes = primes_in_range(0, 5001);
+    for x in range(0u64, 5001) {
+        let expected = primes.iter().take_while(|&&p| p <= x).count();
+        assert_eq!(prime_count(x), expected as u64);
+    }
+}
+
+#[test]
+fn test_powers_of_ten() {
+    // From http://oeis.org/A006880
+    let expected = [0u64, 4, 25, 168, 1229, 9592, 78498, 664579, 5761455,
+                    50847534, 455052511];
+    let mut x = 1u64;
+    for &count in expected.iter() {
+        assert_eq!(prime_count(x), count);
+        x *= 10;
+    }
+}
@@ -1,7 +1,31 @@
 // Implements http://rosettacode.org/wiki/Prime_decomposition
+extern crate num;
+
+use num::bigint::BigUint;
+use num::Integer;
+use std::num::{Zero, One, ToPrimitive, FromPrimitive};
+use std::mem::swap;
+
+// Used by the tests
+#[cfg(test)]
+use std::rand::{task_rng, Rng};
 
 // We need this to be public because it is used from another file
-pub fn factor(mut nb: uint) -> Vec<uint> {
+// Returns the prime factors of nb in increasing order, repeated as many
+// times as they divide nb
+pub fn factor(nb: uint) -> Vec<uint> {
+	let mut result = vec!();
+	for &(p, k) in factorize(nb as u64).iter() {
+		for _ in range(0, k) {
+			result.push(p as uint);
+		}
+	}
+	result
+}
+
+// The plain trial division algorithm. It is fine for small numbers, but
+// it stalls when nb has two large factors
+pub fn factor_trial_division(mut nb: uint) -> Vec<uint> {
 	let mut result = vec!();
 
 	// First we take out all even factors.
@@ -30,6 +54,495 @@ pub fn factor(mut nb: uint) -> Vec<uint> {
 	result
 }
 
+// The primes we take out by trial division before using Pollard's rho.
+// They are also the bases of the Miller-Rabin test, which is deterministic
+// with them for every number below 318665857834031151167461, about
+// 3.18 * 10^23 (http://oeis.org/A014233)
+static SMALL_PRIMES: [u64, ..12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// (a + b) % m without overflowing, for a, b < m
+fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+	if a >= m - b { a - (m - b) } else { a + b }
+}
+
+// (a * b) % m without overflowing, for a, b < m
+fn mul_mod(mut a: u64, mut b: u64, m: u64) -> u64 {
+	if a < (1 << 32) && b < (1 << 32) {
+		return (a * b) % m;
+	}
+
+	// Double and add, like multiplying by hand in binary
+	let mut result = 0;
+	while b > 0 {
+		if b & 1 == 1 {
+			result = add_mod(result, a, m);
+		}
+		a = add_mod(a, a, m);
+		b >>= 1;
+	}
+	result
+}
+
+// (base ^ exp) % m
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+	let mut result = 1 % m;
+	base %= m;
+	while exp > 0 {
+		if exp & 1 == 1 {
+			result = mul_mod(result, base, m);
+		}
+		base = mul_mod(base, base, m);
+		exp >>= 1;
+	}
+	result
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+	while b != 0 {
+		let t = a % b;
+		a = b;
+		b = t;
+	}
+	a
+}
+
+fn abs_diff(a: u64, b: u64) -> u64 {
+	if a > b { a - b } else { b - a }
+}
+
+// Deterministic Miller-Rabin test, correct for every u64
+// Public because primality_trial_div uses it too
+pub fn is_prime(n: u64) -> bool {
+	if n < 2 {
+		return false;
+	}
+	for &p in SMALL_PRIMES.iter() {
+		if n % p == 0 {
+			return n == p;
+		}
+	}
+
+	// n - 1 = d * 2^s with d odd
+	let mut d = n - 1;
+	let mut s = 0u;
+	while d % 2 == 0 {
+		d /= 2;
+		s += 1;
+	}
+
+	SMALL_PRIMES.iter().all(|&a| {
+		let mut x = pow_mod(a, d, n);
+		if x == 1 || x == n - 1 {
+			return true;
+		}
+		for _ in range(1, s) {
+			x = mul_mod(x, x, n);
+			if x == n - 1 {
+				return true;
+			}
+		}
+		false
+	})
+}
+
+// Brent's variant of Pollard's rho. Looks for a factor of the composite
+// number n with the sequence x -> x^2 + c (mod n). Returns None if the
+// sequence cycles before finding one, in which case another c must be tried
+fn pollard_brent(n: u64, c: u64) -> Option<u64> {
+	let step = |x: u64| add_mod(mul_mod(x, x, n), c, n);
+
+	// We multiply this many differences together before taking a gcd
+	let batch = 128u64;
+	let (mut x, mut y, mut ys) = (2u64, 2u64, 2u64);
+	let (mut r, mut q, mut g) = (1u64, 1u64, 1u64);
+
+	while g == 1 {
+		x = y;
+		for _ in range(0, r) {
+			y = step(y);
+		}
+
+		let mut k = 0;
+		while k < r && g == 1 {
+			ys = y;
+			let steps = if batch < r - k { batch } else { r - k };
+			for _ in range(0, steps) {
+				y = step(y);
+				q = mul_mod(q, abs_diff(x, y), n);
+			}
+			g = gcd(q, n);
+			k += batch;
+		}
+		r *= 2;
+	}
+
+	if g == n {
+		// The batch went too far, so we go back and take the steps one by one
+		loop {
+			ys = step(ys);
+			g = gcd(abs_diff(x, ys), n);
+			if g > 1 {
+				break;
+			}
+		}
+	}
+
+	if g == n { None } else { Some(g) }
+}
+
+// Pushes the prime factors of n, which has no factor in SMALL_PRIMES
+fn split(n: u64, factors: &mut Vec<u64>) {
+	if n == 1 {
+		return;
+	}
+	if is_prime(n) {
+		factors.push(n);
+		return;
+	}
+
+	let mut c = 1;
+	loop {
+		match pollard_brent(n, c) {
+			Some(d) => {
+				split(d, factors);
+				split(n / d, factors);
+				return;
+			}
+			None => c += 1
+		}
+	}
+}
+
+// Sorts the prime factors and groups the repeated ones
+fn with_multiplicities<T: Ord + Clone>(mut factors: Vec<T>) -> Vec<(T, uint)> {
+	factors.sort();
+	let mut result = vec!();
+	let mut count = 0u;
+	for i in range(0, factors.len()) {
+		count += 1;
+		if i + 1 == factors.len() || factors.get(i + 1) != factors.get(i) {
+			result.push((factors.get(i).clone(), count));
+			count = 0;
+		}
+	}
+	result
+}
+
+// Returns the prime factors of n in increasing order, each one with its
+// multiplicity. Uses Miller-Rabin and Pollard's rho, so it is fast even for
+// numbers with two large factors
+pub fn factorize(mut n: u64) -> Vec<(u64, uint)> {
+	assert!(n > 0, "0 can't be factorized");
+	let mut factors = vec!();
+
+	for &p in SMALL_PRIMES.iter() {
+		while n % p == 0 {
+			factors.push(p);
+			n /= p;
+		}
+	}
+	split(n, &mut factors);
+
+	with_multiplicities(factors)
+}
+
+fn to_big(n: u64) -> BigUint {
+	FromPrimitive::from_u64(n).unwrap()
+}
+
+// (base ^ exp) % m for big numbers
+fn pow_mod_big(base: &BigUint, exp: &BigUint, m: &BigUint) -> BigUint {
+	let mut result: BigUint = One::one();
+	let mut base = *base % *m;
+	let mut exp = exp.clone();
+	let two = to_big(2);
+	while !exp.is_zero() {
+		if exp.is_odd() {
+			result = (result * base) % *m;
+		}
+		base = (base * base) % *m;
+		exp = exp / two;
+	}
+	result
+}
+
+// The integer square root of a big number, with Newton's method
+pub fn isqrt_big(n: &BigUint) -> BigUint {
+	if n.is_zero() {
+		return Zero::zero();
+	}
+
+	// Start above the root, so every step goes down towards it
+	let one: BigUint = One::one();
+	let mut x = one << ((n.bits() + 1) / 2);
+	loop {
+		let y = (x + *n / x) >> 1;
+		if y >= x {
+			return x;
+		}
+		x = y;
+	}
+}
+
+// (a - b) mod n, for a, b < n
+fn sub_mod_big(a: &BigUint, b: &BigUint, n: &BigUint) -> BigUint {
+	if a >= b { *a - *b } else { *a + *n - *b }
+}
+
+// x / 2 mod n, for odd n
+fn half_mod_big(x: BigUint, n: &BigUint) -> BigUint {
+	if x.is_even() { x >> 1 } else { (x + *n) >> 1 }
+}
+
+// Strong probable prime test to base 2 for big numbers
+fn strong_probable_prime_2(n: &BigUint) -> bool {
+	let one: BigUint = One::one();
+	let n_minus_one = *n - one;
+	let mut d = n_minus_one.clone();
+	let mut s = 0u;
+	while d.is_even() {
+		d = d >> 1;
+		s += 1;
+	}
+
+	let mut x = pow_mod_big(&to_big(2), &d, n);
+	if x == one || x == n_minus_one {
+		return true;
+	}
+	for _ in range(1, s) {
+		x = (x * x) % *n;
+		if x == n_minus_one {
+			return true;
+		}
+	}
+	false
+}
+
+// The Jacobi symbol (a / n) for odd n
+fn jacobi(a: &BigUint, n: &BigUint) -> int {
+	let mut a = *a % *n;
+	let mut n = n.clone();
+	let mut result = 1;
+	let small = |x: &BigUint, m: u64| (*x % to_big(m)).to_u64().unwrap();
+
+	while !a.is_zero() {
+		while a.is_even() {
+			a = a >> 1;
+			let r = small(&n, 8);
+			if r == 3 || r == 5 {
+				result = -result;
+			}
+		}
+		swap(&mut a, &mut n);
+		if small(&a, 4) == 3 && small(&n, 4) == 3 {
+			result = -result;
+		}
+		a = a % n;
+	}
+
+	if n == One::one() { result } else { 0 }
+}
+
+// The bits of n, the most significant first
+fn bits_of(n: &BigUint) -> Vec<bool> {
+	let mut bits = vec!();
+	let mut n = n.clone();
+	while !n.is_zero() {
+		bits.push(n.is_odd());
+		n = n >> 1;
+	}
+	bits.reverse();
+	bits
+}
+
+// Strong Lucas probable prime test with Selfridge's parameters: D is the
+// first of 5, -7, 9, -11, ... with (D / n) = -1, P = 1 and Q = (1 - D) / 4.
+// n must be odd, bigger than any D we try and not a perfect square
+fn strong_lucas_probable_prime(n: &BigUint) -> bool {
+	// D and Q can be negative, so we keep them as residues modulo n
+	let mut abs_d = 5u64;
+	let mut negative = false;
+	let mut d_mod;
+	loop {
+		d_mod = to_big(abs_d) % *n;
+		if negative {
+			d_mod = sub_mod_big(&Zero::zero(), &d_mod, n);
+		}
+		match jacobi(&d_mod, n) {
+			-1 => break,
+			// n has a common factor with D, and n is bigger than D
+			0 => return false,
+			_ => {}
+		}
+		abs_d += 2;
+		negative = !negative;
+	}
+	// Q = (1 - D) / 4, so 4Q = 1 - D. Halving twice divides by 4, as n is odd
+	let q_mod = half_mod_big(half_mod_big(sub_mod_big(&One::one(), &d_mod, n), n), n);
+
+	// n + 1 = d * 2^s with d odd
+	let one: BigUint = One::one();
+	let mut d = *n + one;
+	let mut s = 0u;
+	while d.is_even() {
+		d = d >> 1;
+		s += 1;
+	}
+
+	// Compute U_d, V_d and Q^d going through the bits of d, from
+	// U_1 = 1, V_1 = P = 1 and Q^1 = Q
+	let two = to_big(2);
+	let mut u = one.clone();
+	let mut v = one.clone();
+	let mut qk = q_mod.clone();
+	for bit in bits_of(&d).move_iter().skip(1) {
+		// Double the index: U_2k = U_k V_k and V_2k = V_k^2 - 2Q^k
+		u = (u * v) % *n;
+		v = sub_mod_big(&((v * v) % *n), &((two * qk) % *n), n);
+		qk = (qk * qk) % *n;
+		if bit {
+			// Add one: U_k+1 = (P U_k + V_k) / 2 and V_k+1 = (D U_k + P V_k) / 2
+			let new_u = half_mod_big((u + v) % *n, n);
+			let new_v = half_mod_big((d_mod * u + v) % *n, n);
+			u = new_u;
+			v = new_v;
+			qk = (qk * q_mod) % *n;
+		}
+	}
+
+	if u.is_zero() || v.is_zero() {
+		return true;
+	}
+	// V_2k = V_k^2 - 2Q^k for the other powers of two
+	for _ in range(1, s) {
+		v = sub_mod_big(&((v * v) % *n), &((two * qk) % *n), n);
+		qk = (qk * qk) % *n;
+		if v.is_zero() {
+			return true;
+		}
+	}
+	false
+}
+
+// Baillie-PSW test for big numbers: a strong probable prime test to base 2
+// followed by a strong Lucas test. No composite number is known to pass it,
+// and there are none below 2^64
+pub fn is_prime_big(n: &BigUint) -> bool {
+	match n.to_u64() {
+		Some(small) => return is_prime(small),
+		None => {}
+	}
+	for &p in SMALL_PRIMES.iter() {
+		if (*n % to_big(p)).is_zero() {
+			return false;
+		}
+	}
+
+	// The Lucas test would never find a suitable D for a perfect square
+	let root = isqrt_big(n);
+	if root * root == *n {
+		return false;
+	}
+
+	strong_probable_prime_2(n) && strong_lucas_probable_prime(n)
+}
+
+// Brent's variant of Pollard's rho for big numbers
+fn pollard_brent_big(n: &BigUint, c: &BigUint) -> Option<BigUint> {
+	let step = |x: &BigUint| (*x * *x + *c) % *n;
+	let diff = |a: &BigUint, b: &BigUint| if a > b { *a - *b } else { *b - *a };
+
+	let batch = 128u;
+	let one: BigUint = One::one();
+	let mut x = to_big(2);
+	let mut y = x.clone();
+	let mut ys = x.clone();
+	let mut q = one.clone();
+	let mut g = one.clone();
+	let mut r = 1u;
+
+	while g == one {
+		x = y.clone();
+		for _ in range(0, r) {
+			y = step(&y);
+		}
+
+		let mut k = 0;
+		while k < r && g == one {
+			ys = y.clone();
+			let steps = if batch < r - k { batch } else { r - k };
+			for _ in range(0, steps) {
+				y = step(&y);
+				q = (q * diff(&x, &y)) % *n;
+			}
+			g = q.gcd(n);
+			k += batch;
+		}
+		r *= 2;
+	}
+
+	if g == *n {
+		loop {
+			ys = step(&ys);
+			g = diff(&x, &ys).gcd(n);
+			if g > one {
+				break;
+			}
+		}
+	}
+
+	if g == *n { None } else { Some(g) }
+}
+
+// Pushes the prime factors of n, which has no factor in SMALL_PRIMES
+fn split_big(n: BigUint, factors: &mut Vec<BigUint>) {
+	// Once it is small enough the u64 version is much faster
+	match n.to_u64() {
+		Some(small) => {
+			let mut small_factors = vec!();
+			split(small, &mut small_factors);
+			factors.extend(small_factors.move_iter().map(to_big));
+			return;
+		}
+		None => {}
+	}
+	if is_prime_big(&n) {
+		factors.push(n);
+		return;
+	}
+
+	let mut c: BigUint = One::one();
+	loop {
+		match pollard_brent_big(&n, &c) {
+			Some(d) => {
+				let rest = n / d;
+				split_big(d, factors);
+				split_big(rest, factors);
+				return;
+			}
+			None => c = c + One::one()
+		}
+	}
+}
+
+// Same as factorize, for numbers of any size
+pub fn factorize_big(n: &BigUint) -> Vec<(BigUint, uint)> {
+	assert!(!n.is_zero(), "0 can't be factorized");
+	let mut n = n.clone();
+	let mut factors = vec!();
+
+	for &p in SMALL_PRIMES.iter() {
+		let p = to_big(p);
+		while (n % p).is_zero() {
+			n = n / p;
+			factors.push(p.clone());
+		}
+	}
+	split_big(n, &mut factors);
+
+	with_multiplicities(factors)
+}
+
 // Needed so parallel_calculations compiles cleanly, because it
 // uses this code as a library
 #[allow(dead_code)]
@@ -39,6 +552,7 @@ fn main() {
 	println!("Factors of 15: {}", factor(15));
 	println!("Factors of 16: {}", factor(16));
 	println!("Factors of 10287: {}", factor(10287));
+	println!("Factors of 18446743979220271189: {}", factorize(18446743979220271189));
 }
 
 #[test]
@@ -47,4 +561,94 @@ fn test_basic() {
     assert!(factor(15) == vec!(3, 5));
     assert!(factor(16) == vec!(2, 2, 2, 2));
     assert!(factor(10287) == vec!(3, 3, 3, 3, 127));
+    assert!(factor(1) == vec!());
+}
+
+#[test]
+fn test_is_prime() {
+    let primes: Vec<u64> = range(0u64, 100).filter(|&n| is_prime(n)).collect();
+    assert_eq!(primes.as_slice(), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47,
+                                   53, 59, 61, 67, 71, 73, 79, 83, 89, 97].as_slice());
+
+    assert!(is_prime(4294967291));
+    assert!(is_prime(18446744073709551557)); // The largest u64 prime
+    assert!(!is_prime(18446743979220271189));
+    // Strong pseudoprime to the bases 2, 3, 5, 7, 11, 13, 17, 19 and 23
+    assert!(!is_prime(3825123056546413051));
+}
+
+#[test]
+fn test_large_factors() {
+    assert_eq!(factorize(18446743979220271189), vec!((4294967279, 1), (4294967291, 1)));
+    assert_eq!(factorize(18446744073709551615), // 2^64 - 1
+               vec!((3, 1), (5, 1), (17, 1), (257, 1), (641, 1), (65537, 1), (6700417, 1)));
+    assert_eq!(factorize(1 << 63), vec!((2, 63)));
+    assert_eq!(factorize(1), vec!());
+}
+
+#[test]
+fn test_random_against_trial_division() {
+    let mut rng = task_rng();
+    for _ in range(0u, 200) {
+        // Small enough for trial division to be quick
+        let n = rng.gen_range(1u, 1 << 36);
+        let mut expected = factor_trial_division(n);
+        expected.dedup();
+        let found: Vec<uint> = factorize(n as u64).iter().map(|&(p, _)| p as uint).collect();
+
+        assert_eq!(factor(n), factor_trial_division(n));
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn test_big() {
+    let big = |s: &str| -> BigUint { from_str(s).unwrap() };
+
+    // 9 * (2^61 - 1) * (10^9 + 7) * (2^31 - 1)
+    let n = big("44565841705481992548765593231825115711");
+    assert_eq!(factorize_big(&n), vec!((to_big(3), 2),
+                                       (to_big(1000000007), 1),
+                                       (to_big(2147483647), 1),
+                                       (to_big(2305843009213693951), 1)));
+
+    assert!(is_prime_big(&big("170141183460469231731687303715884105727"))); // 2^127 - 1
+    assert!(!is_prime_big(&n));
+
+    // Small numbers give the same result as the u64 version
+    let expected: Vec<(BigUint, uint)> = factorize(10287).move_iter()
+                                                         .map(|(p, k)| (to_big(p), k))
+                                                         .collect();
+    assert_eq!(factorize_big(&to_big(10287)), expected);
+}
+
+#[test]
+fn test_baillie_psw() {
+    let big = |s: &str| -> BigUint { from_str(s).unwrap() };
+
+    // Same results as Miller-Rabin for small numbers
+    for n in range(0u64, 3000) {
+        assert_eq!(is_prime_big(&to_big(n)), is_prime(n));
+    }
+
+    // 2^89 - 1 and 2^127 - 1 are Mersenne primes
+    assert!(is_prime_big(&big("618970019642690137449562111")));
+    assert!(is_prime_big(&big("170141183460469231731687303715884105727")));
+    // (2^61 - 1) * (2^89 - 1) and (2^89 - 1)^2
+    assert!(!is_prime_big(&big("1427247692705959880439315947500961989719490561")));
+    assert!(!is_prime_big(&big("383123885216472214589586755549637256619304505646776321")));
+    // 2^128 + 1 isn't prime (it is divisible by 59649589127497217)
+    assert!(!is_prime_big(&big("340282366920938463463374607431768211457")));
+    // A strong pseudoprime to all the bases 2 to 37, which Miller-Rabin with
+    // SMALL_PRIMES would take for a prime
+    assert!(!is_prime_big(&big("318665857834031151167461")));
+}
+
+#[test]
+fn test_isqrt_big() {
+    let big: BigUint = from_str("170141183460469231731687303715884105727").unwrap();
+    assert!(isqrt_big(&big) == from_str("13043817825332782212").unwrap());
+    assert!(isqrt_big(&to_big(1125902658307729)) == to_big(33554473));
+    assert!(isqrt_big(&to_big(1125902658307728)) == to_big(33554472));
+    assert!(isqrt_big(&Zero::zero()).is_zero());
 }
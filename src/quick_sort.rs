@@ -1,45 +1,275 @@
 //Implements http://rosettacode.org/wiki/Sorting_algorithms/Quicksort
 
-// Used by the tests
+// Used by the tests and benchmarks
+#[cfg(test)]
+extern crate test;
 #[cfg(test)]
 use std::rand::{task_rng, Rng};
+#[cfg(test)]
+use self::test::Bencher;
+#[cfg(test)]
+use std::cell::RefCell;
+
+use std::os::num_cpus;
+use std::slice::raw::mut_buf_as_slice;
+
+// Slices up to this length are sorted with insertion sort, which is faster
+// than partitioning them
+static INSERTION_THRESHOLD: uint = 16;
+
+// Vectors shorter than this aren't worth sending to another task
+static PARALLEL_THRESHOLD: uint = 10000;
+
+// The sort only looks at the slice through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    fn compare(&mut self, v: &[T], i: uint, j: uint) -> Ordering;
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        v.swap(i, j);
+    }
+}
+
+// The natural order of the values
+struct Plain;
+
+impl<T: TotalOrd> Steps<T> for Plain {
+    fn compare(&mut self, v: &[T], i: uint, j: uint) -> Ordering {
+        v[i].cmp(&v[j])
+    }
+}
+
+// The order of sort_by
+struct By<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering
+}
+
+impl<'a, T> Steps<T> for By<'a, T> {
+    fn compare(&mut self, v: &[T], i: uint, j: uint) -> Ordering {
+        (self.compare)(&v[i], &v[j])
+    }
+}
+
+// Tells the caller of sort_watched about every comparison and swap
+struct Watched<'a, T> {
+    compare: |&T, &T|: 'a -> Ordering,
+    compared: |uint, uint|: 'a,
+    swapped: |uint, uint|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn compare(&mut self, v: &[T], i: uint, j: uint) -> Ordering {
+        (self.compared)(i, j);
+        (self.compare)(&v[i], &v[j])
+    }
+
+    fn swap(&mut self, v: &mut [T], i: uint, j: uint) {
+        (self.swapped)(i, j);
+        v.swap(i, j);
+    }
+}
 
 // We use in place quick sort
 // For details see http://en.wikipedia.org/wiki/Quicksort#In-place_version
 fn quick_sort<T: TotalOrd>(v: &mut[T]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts the slice with the order given by compare
+pub fn sort_by<T>(v: &mut [T], compare: |&T, &T| -> Ordering) {
+    sort_steps(v, &mut By { compare: compare });
+}
+
+// Same as sort_by, and calls compared and swapped with the positions of
+// every comparison and swap
+pub fn sort_watched<T>(v: &mut [T], compare: |&T, &T| -> Ordering,
+                       compared: |uint, uint|, swapped: |uint, uint|) {
+    let mut watched = Watched { compare: compare, compared: compared, swapped: swapped };
+    sort_steps(v, &mut watched);
+}
+
+// This is an introsort (http://en.wikipedia.org/wiki/Introsort): a quicksort
+// which switches to heapsort when the recursion gets too deep, so it is
+// never quadratic
+fn sort_steps<T, S: Steps<T>>(v: &mut [T], steps: &mut S) {
+    // Twice the base 2 logarithm of the length
+    let mut depth_limit = 0;
+    let mut n = v.len();
+    while n > 0 {
+        depth_limit += 2;
+        n >>= 1;
+    }
+
     let len = v.len();
-    if len < 2 {
+    introsort(v, steps, 0, len, depth_limit);
+}
+
+// Sorts v[lo..hi]
+fn introsort<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, hi: uint, depth: uint) {
+    if hi - lo <= INSERTION_THRESHOLD {
+        insertion_sort(v, steps, lo, hi);
         return;
     }
+    if depth == 0 {
+        heap_sort(v, steps, lo, hi);
+        return;
+    }
+
+    choose_pivot(v, steps, lo, hi);
+    let (lt, gt) = partition(v, steps, lo, hi);
 
-    let pivot_index = partition(v);
+    // The values equal to the pivot are already in place
+    introsort(v, steps, lo, lt, depth - 1);
+    introsort(v, steps, gt, hi, depth - 1);
+}
 
-    // Sort the left side
-    quick_sort(v.mut_slice(0, pivot_index));
+// Moves the median of the first, middle and last values of v[lo..hi] to
+// v[lo], so that sorted and reversed inputs get a good pivot
+fn choose_pivot<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, hi: uint) {
+    let (a, b, c) = (lo, lo + (hi - lo) / 2, hi - 1);
+    if steps.compare(v, b, a) == Less {
+        steps.swap(v, a, b);
+    }
+    if steps.compare(v, c, b) == Less {
+        steps.swap(v, b, c);
+        if steps.compare(v, b, a) == Less {
+            steps.swap(v, a, b);
+        }
+    }
+    steps.swap(v, lo, b);
+}
 
-    // Sort the right side
-    quick_sort(v.mut_slice(pivot_index + 1, len));
+// Three-way partition of v[lo..hi] around the pivot at v[lo], so that runs
+// of equal values don't make it quadratic. Returns (lt, gt) such that
+// v[lo..lt] is lower than the pivot, v[lt..gt] is equal to it and v[gt..hi]
+// is bigger
+fn partition<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, hi: uint) -> (uint, uint) {
+    // v[lt] is always a value equal to the pivot
+    let mut lt = lo;
+    let mut i = lo + 1;
+    let mut gt = hi;
+    while i < gt {
+        match steps.compare(v, i, lt) {
+            Less => {
+                steps.swap(v, i, lt);
+                lt += 1;
+                i += 1;
+            }
+            Greater => {
+                gt -= 1;
+                steps.swap(v, i, gt);
+            }
+            Equal => i += 1
+        }
+    }
+    (lt, gt)
 }
 
-// Reorders the slice with values lower than the pivot at the left side,
-// and values bigger than it at the right side.
-// Also returns the store index.
-fn partition<T: TotalOrd>(v: &mut [T]) -> uint {
-    let len = v.len();
-    let pivot_index = len / 2;
+// Sorts v[lo..hi]
+fn insertion_sort<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, hi: uint) {
+    for i in range(lo + 1, hi) {
+        let mut j = i;
+        while j > lo && steps.compare(v, j, j - 1) == Less {
+            steps.swap(v, j, j - 1);
+            j -= 1;
+        }
+    }
+}
 
-    v.swap(pivot_index, len - 1);
+// Sorts v[lo..hi]
+// For details see http://en.wikipedia.org/wiki/Heapsort
+fn heap_sort<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, hi: uint) {
+    let len = hi - lo;
+    // Build a max heap
+    for start in range(0, len / 2).rev() {
+        sift_down(v, steps, lo, start, len);
+    }
+    // Move the largest value to the end, and restore the heap before it
+    for end in range(1, len).rev() {
+        steps.swap(v, lo, lo + end);
+        sift_down(v, steps, lo, 0, end);
+    }
+}
 
-    let mut store_index = 0;
-    for i in range(0, len - 1) {
-        if v[i] <= v[len - 1] {
-            v.swap(i, store_index);
-            store_index += 1;
+// Moves the value at root down the heap in v[lo..lo + end] until its
+// children are lower. root and end are relative to lo
+fn sift_down<T, S: Steps<T>>(v: &mut [T], steps: &mut S, lo: uint, root: uint, end: uint) {
+    let mut root = root;
+    loop {
+        let mut child = 2 * root + 1;
+        if child >= end {
+            return;
+        }
+        if child + 1 < end && steps.compare(v, lo + child, lo + child + 1) == Less {
+            child += 1;
         }
+        if steps.compare(v, lo + root, lo + child) != Less {
+            return;
+        }
+        steps.swap(v, lo + root, lo + child);
+        root = child;
+    }
+}
+
+// Sorts the vector on several tasks. Every partition sends its left side to
+// a new task and sorts the right side itself, until there are about twice
+// as many tasks as CPUs. Then each task goes on with the sequential sort.
+// The values never leave the vector: each task sorts its own part of it
+pub fn parallel_quick_sort<T: TotalOrd + Send>(v: Vec<T>) -> Vec<T> {
+    let mut depth = 1;
+    while (1 << depth) < 2 * num_cpus() {
+        depth += 1;
+    }
+    let mut v = v;
+    parallel_sort(v.as_mut_slice(), depth);
+    v
+}
+
+// Waits for the task sorting the left side of a partition. Dropping it
+// waits too, so that even if this side fails, the slice isn't freed while
+// the other task still uses it
+struct Joined {
+    receiver: Receiver<()>
+}
+
+impl Drop for Joined {
+    fn drop(&mut self) {
+        let _ = self.receiver.recv_opt();
+    }
+}
+
+fn parallel_sort<T: TotalOrd + Send>(v: &mut [T], depth: uint) {
+    let len = v.len();
+    if depth == 0 || len < PARALLEL_THRESHOLD {
+        quick_sort(v);
+        return;
     }
 
-    v.swap(store_index, len - 1);
-    store_index
+    choose_pivot(v, &mut Plain, 0, len);
+    let (lt, gt) = partition(v, &mut Plain, 0, len);
+
+    // The values equal to the pivot are already in place
+    let (left, rest) = v.mut_split_at(lt);
+    let right = rest.mut_slice_from(gt - lt);
+
+    // A task can't borrow the slice, so it gets its address instead. The
+    // left and right sides don't overlap, and we wait for the task before
+    // returning, so it has the left side to itself while it runs
+    let (address, left_len) = (left.as_mut_ptr() as uint, left.len());
+    let (sender, receiver) = channel();
+    let joined = Joined { receiver: receiver };
+    spawn(proc() {
+        unsafe {
+            mut_buf_as_slice(address as *mut T, left_len, |left| {
+                parallel_sort(left, depth - 1);
+            });
+        }
+        sender.send(());
+    });
+    parallel_sort(right, depth - 1);
+
+    // Fails if the other task did
+    joined.receiver.recv();
 }
 
 #[cfg(not(test))]
@@ -57,63 +287,153 @@ fn main() {
 
     quick_sort(strings);
     println!("After: {}", strings.as_slice());
-}
 
-#[cfg(test)]
-fn check_sort<T: TotalOrd>(v: &[T]) {
-    if v.len() > 1 {
-        for i in range(0, v.len()-1) {
-            assert!(v[i] <= v[i+1]);
-        }
-    }
+    // Sort a lot of numbers on every core
+    let many: Vec<int> = range(0, 10000000).map(|i| (i * 7919) % 10000019).collect();
+    let sorted = parallel_quick_sort(many);
+    println!("Sorted {} numbers, from {} to {}",
+             sorted.len(), sorted.get(0), sorted.last().unwrap());
 }
 
 #[test]
-fn test_rosetta_vector() {
-    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+fn test_quick_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
     quick_sort(numbers);
-    check_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
+
+    for input in [vec![], vec![0i], vec![1i, 1, 1, 1, 1], vec![20i, 10, 0, -1, -5]].iter() {
+        let mut numbers = input.clone();
+        let mut expected = input.clone();
+        expected.sort();
+        quick_sort(numbers.as_mut_slice());
+        assert_eq!(numbers, expected);
+    }
 }
 
 #[test]
-fn test_empty_vector() {
-    let mut numbers: ~[int] = ~[];
-    quick_sort(numbers);
-    check_sort(numbers);
+fn test_random_duplicates() {
+    let mut rng = task_rng();
+    for &range in [1, 2, 5, 100].iter() {
+        let mut numbers: Vec<int> = range_vec(&mut rng, 10000, range);
+        let mut expected = numbers.clone();
+        expected.sort();
+        quick_sort(numbers.as_mut_slice());
+        assert_eq!(numbers, expected);
+    }
 }
 
 #[test]
-fn test_one_element_vector() {
-    let mut numbers = [0];
-    quick_sort(numbers);
-    check_sort(numbers);
+fn test_long_patterns() {
+    let n = 100000;
+    let sorted: Vec<int> = range(0, n).collect();
+    let reversed: Vec<int> = range(0, n).rev().collect();
+    // Up and then down, which breaks median of three pivots
+    let organ_pipe: Vec<int> = range(0, n).map(|i| if i < n / 2 { i } else { n - i }).collect();
+    let sawtooth: Vec<int> = range(0, n).map(|i| i % 100).collect();
+
+    for input in [sorted, reversed, organ_pipe, sawtooth].iter() {
+        let mut numbers = input.clone();
+        let mut expected = input.clone();
+        expected.sort();
+        quick_sort(numbers.as_mut_slice());
+        assert_eq!(numbers, expected);
+    }
 }
 
 #[test]
-fn test_repeat_vector() {
-    let mut numbers = [1, 1, 1, 1, 1];
-    quick_sort(numbers);
-    check_sort(numbers);
+fn test_heap_sort() {
+    let mut rng = task_rng();
+    let mut numbers: Vec<int> = rng.gen_iter::<int>().take(1000).collect();
+    let mut expected = numbers.clone();
+    expected.sort();
+    // A depth limit of 0 goes to heapsort right away
+    introsort(numbers.as_mut_slice(), &mut Plain, 0, 1000, 0);
+    assert_eq!(numbers, expected);
 }
 
 #[test]
-fn test_worst_case_vector() {
-    let mut numbers = [20, 10, 0, -1, -5];
-    quick_sort(numbers);
-    check_sort(numbers);
+fn test_sort_by() {
+    let mut numbers = [4, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    sort_by(numbers, |a, b| b.cmp(a));
+    assert_eq!(numbers.as_slice(), [782, 99, 83, 65, 4, 2, 2, 1, 0, -31].as_slice());
+
+    let mut words = ["beach", "hotel", "airplane", "car", "house", "art"];
+    sort_by(words, |a, b| match a.len().cmp(&b.len()) {
+        Equal => a.cmp(b),
+        order => order
+    });
+    assert_eq!(words.as_slice(), ["art", "car", "beach", "hotel", "house", "airplane"].as_slice());
 }
 
 #[test]
-fn test_already_sorted_vector() {
-    let mut numbers = [-1, 0, 3, 6, 99];
-    quick_sort(numbers);
-    check_sort(numbers);
+fn test_sort_watched() {
+    let steps = RefCell::new(vec![]);
+    // Short slices use insertion sort
+    let mut numbers = [3i, 1, 2];
+    sort_watched(numbers, |a, b| a.cmp(b),
+                 |i, j| steps.borrow_mut().push(("compare", i, j)),
+                 |i, j| steps.borrow_mut().push(("swap", i, j)));
+    assert_eq!(numbers.as_slice(), [1i, 2, 3].as_slice());
+    assert_eq!(*steps.borrow(), vec![("compare", 1, 0), ("swap", 1, 0), ("compare", 2, 1),
+                                     ("swap", 2, 1), ("compare", 1, 0)]);
+}
+
+// Random numbers in [0, range)
+#[cfg(test)]
+fn range_vec<R: Rng>(rng: &mut R, len: uint, range: int) -> Vec<int> {
+    Vec::from_fn(len, |_| rng.gen_range(0, range))
+}
+
+#[bench]
+fn bench_random(b: &mut Bencher) {
+    let numbers: Vec<int> = task_rng().gen_iter::<int>().take(100000).collect();
+    b.iter(|| {
+        let mut v = numbers.clone();
+        quick_sort(v.as_mut_slice());
+        v
+    });
+}
+
+// With the old two-way partition every run of equal values went to the same
+// side, which made this quadratic
+#[bench]
+fn bench_duplicates(b: &mut Bencher) {
+    let numbers = range_vec(&mut task_rng(), 100000, 4);
+    b.iter(|| {
+        let mut v = numbers.clone();
+        quick_sort(v.as_mut_slice());
+        v
+    });
 }
 
 #[test]
-fn test_random_numbers() {
+fn test_parallel() {
     let mut rng = task_rng();
-    let mut numbers : Vec<int> = rng.gen_iter::<int>().take(500).collect();
-    quick_sort(numbers.as_mut_slice());
-    check_sort(numbers.as_mut_slice());
+    let random: Vec<int> = rng.gen_iter::<int>().take(200000).collect();
+    let duplicates = range_vec(&mut rng, 200000, 10);
+    let sorted: Vec<int> = range(0, 200000).collect();
+
+    for input in [random, duplicates, sorted, vec![], vec![3, 1, 2]].iter() {
+        let mut expected = input.clone();
+        expected.sort();
+        assert_eq!(parallel_quick_sort(input.clone()), expected);
+    }
+}
+
+#[bench]
+fn bench_sequential_million(b: &mut Bencher) {
+    let numbers: Vec<int> = task_rng().gen_iter::<int>().take(1000000).collect();
+    b.iter(|| {
+        let mut v = numbers.clone();
+        quick_sort(v.as_mut_slice());
+        v
+    });
+}
+
+#[bench]
+fn bench_parallel_million(b: &mut Bencher) {
+    let numbers: Vec<int> = task_rng().gen_iter::<int>().take(1000000).collect();
+    b.iter(|| {
+        parallel_quick_sort(numbers.clone())
+    });
 }
@@ -0,0 +1,115 @@
+// Implements http://rosettacode.org/wiki/Sorting_algorithms/Radix_sort
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::int;
+
+// The sort only looks at the values through these, so that it can be
+// watched. The plain sort doesn't watch it, and costs nothing more
+trait Steps<T> {
+    // An unsigned key which sorts the same way as the value
+    fn key(&mut self, value: &T) -> u64;
+
+    fn write(&mut self, v: &mut [T], i: uint, value: T) {
+        v[i] = value;
+    }
+}
+
+struct Plain;
+
+impl Steps<int> for Plain {
+    // Flipping the sign bit puts the negative numbers first
+    fn key(&mut self, value: &int) -> u64 {
+        (*value as u64) ^ (1 << 63)
+    }
+}
+
+// Tells the caller of sort_watched about every write
+struct Watched<'a, T> {
+    key: |&T|: 'a -> u64,
+    written: |uint, &T|: 'a
+}
+
+impl<'a, T> Steps<T> for Watched<'a, T> {
+    fn key(&mut self, value: &T) -> u64 {
+        (self.key)(value)
+    }
+
+    fn write(&mut self, v: &mut [T], i: uint, value: T) {
+        (self.written)(i, &value);
+        v[i] = value;
+    }
+}
+
+fn radix_sort(v: &mut [int]) {
+    sort_steps(v, &mut Plain);
+}
+
+// Sorts v in the order of the keys, and calls written with every value
+// written to v. Radix sort never compares two values, so there is no
+// comparison to report, and no other order it could sort in
+pub fn sort_watched<T: Clone>(v: &mut [T], key: |&T| -> u64, written: |uint, &T|) {
+    let mut watched = Watched { key: key, written: written };
+    sort_steps(v, &mut watched);
+}
+
+// Least significant digit radix sort, with one byte of the key per pass.
+// Every pass is a stable counting sort, so the order of the previous
+// passes is kept for equal bytes
+fn sort_steps<T: Clone, S: Steps<T>>(v: &mut [T], steps: &mut S) {
+    let len = v.len();
+    for pass in range(0u, 8) {
+        let shift = 8 * pass;
+        let digits: Vec<uint> = v.iter()
+                                 .map(|x| ((steps.key(x) >> shift) & 0xff) as uint)
+                                 .collect();
+
+        // counts[d + 1] is the number of values with digit d
+        let mut counts = [0u, ..257];
+        for &d in digits.iter() {
+            counts[d + 1] += 1;
+        }
+        // If every value has the same digit, the pass wouldn't move anything
+        if counts.iter().any(|&c| c == len) {
+            continue;
+        }
+
+        // Now counts[d] is where the values with digit d start
+        for d in range(0u, 256) {
+            counts[d + 1] += counts[d];
+        }
+        let copy = v.to_vec();
+        for (x, &d) in copy.move_iter().zip(digits.iter()) {
+            steps.write(v, counts[d], x);
+            counts[d] += 1;
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    radix_sort(numbers);
+    println!("{}", numbers.as_slice());
+}
+
+#[test]
+fn test_radix_sort() {
+    let mut numbers = [4i, 65, 2, -31, 0, 99, 2, 83, 782, 1];
+    radix_sort(numbers);
+    assert_eq!(numbers.as_slice(), [-31i, 0, 1, 2, 2, 4, 65, 83, 99, 782].as_slice());
+
+    let mut extremes = [0i, int::MAX, -1, int::MIN, 1];
+    radix_sort(extremes);
+    assert_eq!(extremes.as_slice(), [int::MIN, -1, 0, 1, int::MAX].as_slice());
+}
+
+#[test]
+fn test_sort_watched() {
+    let writes = RefCell::new(vec![]);
+    // Sorted by the last digit only, which keeps the order of 21 and 1
+    let mut numbers = [21u, 3, 1];
+    sort_watched(numbers, |&x| (x % 10) as u64, |i, &x| writes.borrow_mut().push((i, x)));
+    assert_eq!(numbers.as_slice(), [21u, 1, 3].as_slice());
+    assert_eq!(*writes.borrow(), vec![(0, 21), (2, 3), (1, 1)]);
+}
@@ -1,6 +1,9 @@
 // Implements http://rosettacode.org/wiki/Sieve_of_Eratosthenes
 
 use std::iter::{range_inclusive, range_step};
+use std::cmp::max;
+use std::num::{CheckedAdd, CheckedMul};
+use std::u64;
 
 fn int_sqrt(n: uint) -> uint {
     (n as f64).sqrt() as uint
@@ -15,7 +18,7 @@ fn simple_sieve(limit: uint) -> Vec<uint> {
     let mut primes = Vec::from_fn(limit + 1, |_| true);
 
     for prime in range_inclusive(2, int_sqrt(limit) + 1) {
-        if *primes.get(0) {
+        if *primes.get(prime) {
             for multiple in range_step(prime * prime, limit + 1, prime) {
                 *primes.get_mut(multiple) = false
             }
@@ -25,13 +28,298 @@ fn simple_sieve(limit: uint) -> Vec<uint> {
     range_inclusive(2, limit).filter(|&n| *primes.get(n)).collect()
 }
 
+// The segmented sieve below only keeps track of the numbers which are
+// coprime to 2, 3 and 5. In every block of 30 numbers there are 8 of them,
+// so each block fits in a byte, with one bit per residue
+static WHEEL: [u64, ..8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+// Distance from each residue of the wheel to the next one
+static WHEEL_GAPS: [u64, ..8] = [6, 4, 2, 4, 2, 4, 6, 2];
+
+// The bit used by each residue modulo 30, or NONE if the residue isn't on
+// the wheel
+static NONE: u8 = 0xff;
+static WHEEL_BIT: [u8, ..30] = [
+    NONE, 0, NONE, NONE, NONE, NONE, NONE, 1, NONE, NONE,
+    NONE, 2, NONE, 3, NONE, NONE, NONE, 4, NONE, 5,
+    NONE, NONE, NONE, 6, NONE, NONE, NONE, NONE, NONE, 7];
+
+// Bytes per segment. 32KB fit in the L1 cache of most CPUs, and cover
+// almost a million numbers
+static SEGMENT_BYTES: uint = 32 * 1024;
+
+// The largest u64 whose square is still a u64
+static MAX_U64_ROOT: u64 = 4294967295;
+
+// The integer square root of n, without the rounding errors of f64 for
+// large numbers. Near the top of the range f64 rounds the root up to 2^32,
+// so we clamp it, and compare with divisions that can't overflow
+pub fn int_sqrt_u64(n: u64) -> u64 {
+    let mut r = (n as f64).sqrt() as u64;
+    if r > MAX_U64_ROOT {
+        r = MAX_U64_ROOT;
+    }
+    while r > 0 && r > n / r {
+        r -= 1;
+    }
+    while r < MAX_U64_ROOT && r + 1 <= n / (r + 1) {
+        r += 1;
+    }
+    r
+}
+
+// Primes bigger than 5 and up to limit, which are used to sieve
+// the segments
+fn sieving_primes(limit: u64) -> Vec<u64> {
+    simple_sieve(limit as uint).move_iter()
+                               .filter(|&p| p > 5)
+                               .map(|p| p as u64)
+                               .collect()
+}
+
+// Sieves the numbers in [lo, hi) where lo is a multiple of 30, and
+// pushes the primes bigger than 5 to out. base must contain all the
+// primes bigger than 5 up to the square root of hi
+fn sieve_segment(lo: u64, hi: u64, base: &[u64], bytes: &mut [u8], out: &mut Vec<u64>) {
+    let len = ((hi - lo + 29) / 30) as uint;
+    let bytes = bytes.mut_slice_to(len);
+    for b in bytes.mut_iter() {
+        *b = 0xff;
+    }
+    if lo == 0 {
+        // 1 is on the wheel, but it isn't a prime
+        bytes[0] &= !1;
+    }
+
+    for &p in base.iter() {
+        // p * p >= hi, written so that it can't overflow
+        if p > (hi - 1) / p {
+            break;
+        }
+
+        // Only multiples p * m with m on the wheel need to be crossed out,
+        // and those with m < p were crossed out by smaller primes
+        let mut m = max(p, lo / p + if lo % p == 0 { 0 } else { 1 });
+        while WHEEL_BIT[(m % 30) as uint] == NONE {
+            m += 1;
+        }
+        let mut bit = WHEEL_BIT[(m % 30) as uint] as uint;
+
+        loop {
+            // Near the end of u64 the product may not fit
+            let n = match p.checked_mul(&m) {
+                Some(n) if n < hi => n,
+                _ => break
+            };
+            let i = ((n - lo) / 30) as uint;
+            bytes[i] &= !(1 << WHEEL_BIT[(n % 30) as uint]);
+
+            m += WHEEL_GAPS[bit];
+            bit = (bit + 1) % 8;
+        }
+    }
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == 0 {
+            continue;
+        }
+        for bit in range(0u, 8) {
+            if b & (1 << bit) != 0 {
+                // Compared before adding the residue, which may not fit
+                let block = lo + 30 * i as u64;
+                if WHEEL[bit] < hi - block {
+                    out.push(block + WHEEL[bit]);
+                }
+            }
+        }
+    }
+}
+
+// Returns the primes in [lo, hi), sieving one segment at a time. Only the
+// primes up to the square root of hi and one segment are kept in memory
+// besides the result, so it works for ranges around 10^12 and beyond
+pub fn primes_in_range(lo: u64, hi: u64) -> Vec<u64> {
+    let mut result = vec!();
+    if hi <= lo {
+        return result;
+    }
+
+    for &p in [2u64, 3, 5].iter() {
+        if lo <= p && p < hi {
+            result.push(p);
+        }
+    }
+
+    let base = sieving_primes(int_sqrt_u64(hi));
+    let mut bytes = Vec::from_elem(SEGMENT_BYTES, 0u8);
+    let mut segment = vec!();
+    let mut seg_lo = lo - lo % 30;
+    while seg_lo < hi {
+        let seg_hi = match seg_lo.checked_add(&(30 * SEGMENT_BYTES as u64)) {
+            Some(seg_hi) if seg_hi < hi => seg_hi,
+            _ => hi
+        };
+        segment.clear();
+        sieve_segment(seg_lo, seg_hi, base.as_slice(), bytes.as_mut_slice(), &mut segment);
+        result.extend(segment.iter().map(|&p| p).filter(|&p| p >= lo));
+        seg_lo = seg_hi;
+    }
+
+    result
+}
+
+// Iterator over the prime numbers that fit in a u64, sieving one segment
+// at a time
+pub struct Primes {
+    // The primes of the current segment which haven't been returned yet
+    segment: Vec<u64>,
+    pos: uint,
+    // Start of the next segment to sieve, or None once the last segment,
+    // which ends at u64::MAX, has been sieved. u64::MAX isn't a prime
+    next_lo: Option<u64>,
+    // The primes used to sieve, and the limit up to which we have them
+    base: Vec<u64>,
+    base_limit: u64,
+    bytes: Vec<u8>
+}
+
+impl Primes {
+    pub fn new() -> Primes {
+        Primes {
+            // 2, 3 and 5 aren't on the wheel, so we give them first
+            segment: vec!(2, 3, 5),
+            pos: 0,
+            next_lo: Some(0),
+            base: vec!(),
+            base_limit: 0,
+            bytes: Vec::from_elem(SEGMENT_BYTES, 0u8)
+        }
+    }
+
+    fn sieve_next_segment(&mut self, lo: u64) {
+        let hi = lo.checked_add(&(30 * SEGMENT_BYTES as u64)).unwrap_or(u64::MAX);
+
+        // Get more sieving primes when needed. We ask for twice as many as
+        // we need, so that we don't have to do it for every segment
+        let needed = int_sqrt_u64(hi);
+        if needed > self.base_limit {
+            self.base_limit = 2 * needed;
+            self.base = sieving_primes(self.base_limit);
+        }
+
+        self.segment.clear();
+        self.pos = 0;
+        sieve_segment(lo, hi, self.base.as_slice(), self.bytes.as_mut_slice(),
+                      &mut self.segment);
+        self.next_lo = if hi == u64::MAX { None } else { Some(hi) };
+    }
+}
+
+impl Iterator<u64> for Primes {
+    fn next(&mut self) -> Option<u64> {
+        while self.pos == self.segment.len() {
+            match self.next_lo {
+                Some(lo) => self.sieve_next_segment(lo),
+                None => return None
+            }
+        }
+        self.pos += 1;
+        Some(*self.segment.get(self.pos - 1))
+    }
+}
+
 #[cfg(not(test))]
 fn main() {
-    println!("{}", simple_sieve(100))
+    println!("{}", simple_sieve(100));
+
+    println!("The 1000000th prime is {}", Primes::new().nth(999999).unwrap());
+
+    let trillion = 1000000000000;
+    println!("Primes between 10^12 and 10^12 + 100: {}",
+             primes_in_range(trillion, trillion + 100));
 }
 
 #[test]
 fn test_basic() {
     let primes = simple_sieve(30);
     assert!(primes.as_slice() == [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_range_against_simple_sieve() {
+    for limit in range(0u, 500).chain(range_step(500u, 5000, 97)) {
+        let expected: Vec<u64> = simple_sieve(limit).move_iter().map(|p| p as u64).collect();
+        assert_eq!(primes_in_range(0, limit as u64 + 1), expected);
+    }
+
+    // Ranges which don't start at 0
+    let all: Vec<u64> = simple_sieve(3000).move_iter().map(|p| p as u64).collect();
+    for &(lo, hi) in [(1u64, 2u64), (2, 3), (3, 30), (29, 31), (31, 61), (1000, 3001)].iter() {
+        let expected: Vec<u64> = all.iter().map(|&p| p)
+                                    .filter(|&p| lo <= p && p < hi)
+                                    .collect();
+        assert_eq!(primes_in_range(lo, hi), expected);
+    }
+    assert_eq!(primes_in_range(10, 5), vec!());
+}
+
+#[test]
+fn test_several_segments() {
+    // Long enough to need a few segments
+    let limit = 3 * 30 * SEGMENT_BYTES + 12345;
+    let expected: Vec<u64> = simple_sieve(limit).move_iter().map(|p| p as u64).collect();
+    assert!(primes_in_range(0, limit as u64 + 1) == expected);
+
+    let iterated: Vec<u64> = Primes::new().take(expected.len()).collect();
+    assert!(iterated == expected);
+}
+
+#[test]
+fn test_int_sqrt_u64() {
+    assert_eq!(int_sqrt_u64(0), 0);
+    assert_eq!(int_sqrt_u64(1), 1);
+    assert_eq!(int_sqrt_u64(15), 3);
+    assert_eq!(int_sqrt_u64(16), 4);
+    assert_eq!(int_sqrt_u64(1125902658307729), 33554473);
+    assert_eq!(int_sqrt_u64(1125902658307728), 33554472);
+    // (2^32 - 1)^2 and everything above it
+    assert_eq!(int_sqrt_u64(18446744065119617025), 4294967295);
+    assert_eq!(int_sqrt_u64(18446744065119617024), 4294967294);
+    assert_eq!(int_sqrt_u64(18446744073709551615), 4294967295);
+}
+
+#[test]
+fn test_iterator() {
+    let primes: Vec<u64> = Primes::new().take(10).collect();
+    assert_eq!(primes, vec!(2, 3, 5, 7, 11, 13, 17, 19, 23, 29));
+    assert_eq!(Primes::new().nth(9999), Some(104729));
+}
+
+#[test]
+fn test_around_a_trillion() {
+    let trillion = 1000000000000;
+    let primes = primes_in_range(trillion, trillion + 10000);
+    assert_eq!(primes.len(), 335);
+    assert_eq!(primes.slice_to(3), [1000000000039, 1000000000061, 1000000000063].as_slice());
+    assert_eq!(*primes.last().unwrap(), 1000000009999);
+}
+
+#[test]
+fn test_end_of_u64() {
+    // Sieving the whole segment would need the primes up to 2^32, so this
+    // only uses the small ones, and checks that nothing overflows: the
+    // numbers left are those coprime to 30 that no base prime divides
+    let base = sieving_primes(1000);
+    let hi = u64::MAX;
+    let lo = hi - hi % 30 - 30 * 100;
+    let mut bytes = Vec::from_elem(SEGMENT_BYTES, 0u8);
+    let mut left = vec!();
+    sieve_segment(lo, hi, base.as_slice(), bytes.as_mut_slice(), &mut left);
+
+    let expected: Vec<u64> = range(lo, hi).filter(|&n| {
+        n % 2 != 0 && n % 3 != 0 && n % 5 != 0 && base.iter().all(|&p| n % p != 0)
+    }).collect();
+    assert_eq!(left, expected);
+    // The largest u64 prime is among them
+    assert!(left.contains(&18446744073709551557));
+}
@@ -0,0 +1,276 @@
+// A common interface for the sorting algorithms, so that they can share
+// their tests and be compared with each other. Each sort is in its own task
+// file, and can tell a Recorder about every step it takes
+// Used by compare_sorting_algorithms and sorting_visualisation
+
+use std::cell::RefCell;
+use std::mem::replace;
+use std::rand::{task_rng, Rng};
+
+// What a sort did to the slice
+#[deriving(Show, PartialEq, Clone)]
+pub struct Counts {
+    pub comparisons: uint,
+    pub swaps: uint,
+    pub writes: uint
+}
+
+// Something a sort did to the slice. The sorts which work from a copy of
+// the slice compare the positions of the copy, which hold the values the
+// slice had when the copy was made
+#[deriving(Show, PartialEq, Clone)]
+pub enum Event<T> {
+    Compare(uint, uint),
+    Swap(uint, uint),
+    Write(uint, T)
+}
+
+// Counts the steps a sort tells it about, and logs them if asked to. The
+// sorts report through several closures at once, so it only needs to be
+// borrowed immutably
+pub struct Recorder<T> {
+    counts: RefCell<Counts>,
+    log: RefCell<Option<Vec<Event<T>>>>
+}
+
+impl<T: Clone> Recorder<T> {
+    pub fn new() -> Recorder<T> {
+        Recorder {
+            counts: RefCell::new(Counts { comparisons: 0, swaps: 0, writes: 0 }),
+            log: RefCell::new(None)
+        }
+    }
+
+    // Same as new, but every event is logged
+    pub fn logging() -> Recorder<T> {
+        let recorder = Recorder::new();
+        *recorder.log.borrow_mut() = Some(vec![]);
+        recorder
+    }
+
+    fn record(&self, event: Event<T>) {
+        match *self.log.borrow_mut() {
+            Some(ref mut log) => log.push(event),
+            None => {}
+        }
+    }
+
+    pub fn compared(&self, i: uint, j: uint) {
+        self.counts.borrow_mut().comparisons += 1;
+        self.record(Compare(i, j));
+    }
+
+    pub fn swapped(&self, i: uint, j: uint) {
+        self.counts.borrow_mut().swaps += 1;
+        self.record(Swap(i, j));
+    }
+
+    pub fn written(&self, i: uint, value: &T) {
+        self.counts.borrow_mut().writes += 1;
+        // Only clone the value if it is going to be logged
+        let logging = self.log.borrow().is_some();
+        if logging {
+            self.record(Write(i, value.clone()));
+        }
+    }
+
+    pub fn counts(&self) -> Counts {
+        self.counts.borrow().clone()
+    }
+
+    // The events logged so far, if we are logging
+    pub fn take_events(&self) -> Vec<Event<T>> {
+        match *self.log.borrow_mut() {
+            Some(ref mut log) => replace(log, vec![]),
+            None => vec![]
+        }
+    }
+}
+
+// The order the sorters sort in. Radix sort doesn't compare values, so
+// they all sort by an unsigned key, which sorts the same way as the values
+pub trait Key {
+    fn key(&self) -> u64;
+}
+
+impl Key for uint {
+    fn key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl Key for u64 {
+    fn key(&self) -> u64 {
+        *self
+    }
+}
+
+impl Key for int {
+    // Flipping the sign bit puts the negative numbers first
+    fn key(&self) -> u64 {
+        (*self as u64) ^ (1 << 63)
+    }
+}
+
+pub trait Sorter {
+    fn name(&self) -> &'static str;
+
+    // Whether equal keys keep their order
+    fn is_stable(&self) -> bool;
+
+    // Sorts v by key, and tells the recorder about every step
+    fn sort<T: Clone + Key>(&self, v: &mut [T], recorder: &Recorder<T>);
+}
+
+// Sorts v, and returns what it took
+pub fn sort_counting<S: Sorter, T: Clone + Key>(sorter: &S, v: &mut [T]) -> Counts {
+    let recorder = Recorder::new();
+    sorter.sort(v, &recorder);
+    recorder.counts()
+}
+
+// Sorts v, and returns everything the sort did, in order
+pub fn sort_logged<S: Sorter, T: Clone + Key>(sorter: &S, v: &mut [T]) -> Vec<Event<T>> {
+    let recorder = Recorder::logging();
+    sorter.sort(v, &recorder);
+    recorder.take_events()
+}
+
+// The inputs of the comparison
+#[deriving(Show)]
+pub enum Input {
+    Random,
+    Sorted,
+    Reversed,
+    // Random values out of only ten different ones
+    Duplicates
+}
+
+pub static INPUTS: [Input, ..4] = [Random, Sorted, Reversed, Duplicates];
+
+pub fn make_input(input: Input, len: uint) -> Vec<int> {
+    let mut rng = task_rng();
+    match input {
+        Random => rng.gen_iter::<int>().take(len).collect(),
+        Sorted => range(0, len as int).collect(),
+        Reversed => range(0, len as int).rev().collect(),
+        Duplicates => Vec::from_fn(len, |_| rng.gen_range(0, 10))
+    }
+}
+
+// What the sort takes on each of the inputs
+pub fn measure<S: Sorter>(sorter: &S, len: uint) -> Vec<Counts> {
+    INPUTS.iter().map(|&input| {
+        let mut v = make_input(input, len);
+        sort_counting(sorter, v.as_mut_slice())
+    }).collect()
+}
+
+// One line of the comparison table
+pub fn table_row<S: Sorter>(sorter: &S, len: uint) -> String {
+    let mut row = format!("{:<16}", sorter.name());
+    for counts in measure(sorter, len).iter() {
+        row.push_str(format!(" | {:>10} {:>10} {:>10}",
+                             counts.comparisons, counts.swaps, counts.writes).as_slice());
+    }
+    row
+}
+
+pub fn table_header() -> String {
+    let mut header = format!("{:<16}", "");
+    for input in INPUTS.iter() {
+        header.push_str(format!(" | {:<32}", input.to_str()).as_slice());
+    }
+    header.push_str(format!("\n{:<16}", "").as_slice());
+    for _ in INPUTS.iter() {
+        header.push_str(format!(" | {:>10} {:>10} {:>10}", "compare", "swap", "write").as_slice());
+    }
+    header
+}
+
+// A record sorted by key only, so we can see whether records with the
+// same key keep their order
+#[deriving(Clone, Show)]
+pub struct Record {
+    pub key: uint,
+    pub index: uint
+}
+
+impl Key for Record {
+    fn key(&self) -> u64 {
+        self.key as u64
+    }
+}
+
+// The tests every sort has to pass
+#[cfg(test)]
+pub fn check_sorter<S: Sorter>(sorter: &S) {
+    // The cases bubble sort and quick sort were tested with
+    let mut cases: Vec<Vec<int>> = vec![vec![4, 65, 2, -31, 0, 99, 2, 83, 782, 1],
+                                        vec![],
+                                        vec![0],
+                                        vec![1, 1, 1, 1, 1],
+                                        vec![20, 10, 0, -1, -5],
+                                        vec![-1, 0, 3, 6, 99]];
+    for len in range(0u, 50).chain([500u, 1000].iter().map(|&len| len)) {
+        for input in INPUTS.iter() {
+            cases.push(make_input(*input, len));
+        }
+    }
+
+    for case in cases.iter() {
+        let mut v = case.clone();
+        let mut expected = case.clone();
+        expected.sort();
+        sort_counting(sorter, v.as_mut_slice());
+        assert_eq!(v, expected);
+    }
+
+    // Few different keys, so there are a lot of equal ones
+    let mut rng = task_rng();
+    let mut records: Vec<Record> = range(0, 1000).map(|i| {
+        Record { key: rng.gen_range(0u, 10), index: i }
+    }).collect();
+    sort_counting(sorter, records.as_mut_slice());
+    for pair in records.as_slice().windows(2) {
+        assert!(pair[0].key <= pair[1].key);
+        if sorter.is_stable() && pair[0].key == pair[1].key {
+            assert!(pair[0].index < pair[1].index);
+        }
+    }
+}
+
+// This file is only used as a module
+#[allow(dead_code)]
+#[cfg(not(test))]
+fn main() {}
+
+#[test]
+fn test_counts() {
+    let recorder = Recorder::new();
+    recorder.compared(0, 1);
+    recorder.swapped(0, 1);
+    recorder.compared(1, 2);
+    recorder.written(2, &7i);
+    assert_eq!(recorder.counts(), Counts { comparisons: 2, swaps: 1, writes: 1 });
+    // Nothing is logged unless we ask for it
+    assert!(recorder.take_events().is_empty());
+}
+
+#[test]
+fn test_logging() {
+    let recorder = Recorder::logging();
+    recorder.compared(0, 1);
+    recorder.swapped(0, 1);
+    recorder.written(2, &7i);
+    assert_eq!(recorder.take_events(), vec![Compare(0, 1), Swap(0, 1), Write(2, 7)]);
+    assert!(recorder.take_events().is_empty());
+    assert_eq!(recorder.counts().swaps, 1);
+}
+
+#[test]
+fn test_key() {
+    let mut keys: Vec<u64> = [3i, -1, 0, -100, 7].iter().map(|x| x.key()).collect();
+    keys.sort();
+    assert_eq!(keys, vec![(-100i).key(), (-1i).key(), 0i.key(), 3i.key(), 7i.key()]);
+}
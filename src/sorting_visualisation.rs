@@ -0,0 +1,256 @@
+// Shows how the sorts work, one comparison or swap at a time, as ASCII bar
+// charts or as an animated SVG
+// See http://rosettacode.org/wiki/Sorting_algorithms/Bubble_sort and
+// http://rosettacode.org/wiki/Sorting_algorithms/Quicksort
+use std::cmp::max;
+use sorting::{Event, Compare, Swap, Write, Recorder};
+#[cfg(not(test))]
+use std::rand::{task_rng, Rng};
+
+// We only need the events and their recorder from this module
+#[allow(dead_code)]
+mod sorting;
+#[allow(dead_code)]
+mod bubble_sort;
+#[allow(dead_code)]
+mod quick_sort;
+
+// The sorts we can show
+pub enum Sort {
+    Bubble,
+    Quick
+}
+
+// Sorts the values, and returns everything the sort did, in order
+pub fn sort_logged(sort: Sort, values: &mut [uint]) -> Vec<Event<uint>> {
+    let recorder = Recorder::logging();
+    match sort {
+        Bubble => bubble_sort::sort_watched(values, |a, b| a.cmp(b),
+                                            |i, j| recorder.compared(i, j),
+                                            |i, j| recorder.swapped(i, j)),
+        Quick => quick_sort::sort_watched(values, |a, b| a.cmp(b),
+                                          |i, j| recorder.compared(i, j),
+                                          |i, j| recorder.swapped(i, j))
+    }
+    recorder.take_events()
+}
+
+// The values after an event, and the positions it touched
+pub struct Frame {
+    pub values: Vec<uint>,
+    pub event: Option<Event<uint>>
+}
+
+impl Frame {
+    // Whether the event of the frame compared the value at i, or moved it
+    fn compared(&self, i: uint) -> bool {
+        match self.event {
+            Some(Compare(a, b)) => a == i || b == i,
+            _ => false
+        }
+    }
+
+    fn moved(&self, i: uint) -> bool {
+        match self.event {
+            Some(Swap(a, b)) => a == i || b == i,
+            Some(Write(a, _)) => a == i,
+            _ => false
+        }
+    }
+
+    fn caption(&self) -> String {
+        match self.event {
+            None => "start".to_string(),
+            Some(Compare(a, b)) => format!("compare {} and {}", a, b),
+            Some(Swap(a, b)) => format!("swap {} and {}", a, b),
+            Some(Write(a, value)) => format!("write {} at {}", value, a)
+        }
+    }
+}
+
+// Replays the events on the initial values. The first frame has the
+// initial values, then there is a frame per event
+pub fn frames(initial: &[uint], events: &[Event<uint>]) -> Vec<Frame> {
+    let mut values = initial.to_vec();
+    let mut frames = vec![Frame { values: values.clone(), event: None }];
+    for event in events.iter() {
+        match *event {
+            Compare(..) => {}
+            Swap(a, b) => values.as_mut_slice().swap(a, b),
+            Write(a, value) => *values.get_mut(a) = value
+        }
+        frames.push(Frame { values: values.clone(), event: Some(event.clone()) });
+    }
+    frames
+}
+
+// Draws the frame as columns of characters, height lines high. The values
+// being compared are drawn with '*' and those being moved with '@'
+pub fn render_ascii(frame: &Frame, height: uint) -> String {
+    let largest = max(frame.values.iter().map(|&x| x).max().unwrap_or(0), 1);
+    // Round up, so only 0 has an empty bar
+    let bars: Vec<uint> = frame.values.iter()
+                                      .map(|&x| (x * height + largest - 1) / largest)
+                                      .collect();
+
+    let mut out = String::new();
+    for level in range(0, height).rev() {
+        for (i, &bar) in bars.iter().enumerate() {
+            out.push_char(if bar <= level {
+                ' '
+            } else if frame.moved(i) {
+                '@'
+            } else if frame.compared(i) {
+                '*'
+            } else {
+                '#'
+            });
+        }
+        out.push_char('\n');
+    }
+    out.push_str(frame.caption().as_slice());
+    out.push_char('\n');
+    out
+}
+
+static BAR_WIDTH: uint = 10;
+static SVG_HEIGHT: uint = 200;
+
+fn bar_color(frame: &Frame, i: uint) -> &'static str {
+    if frame.moved(i) {
+        "red"
+    } else if frame.compared(i) {
+        "orange"
+    } else {
+        "steelblue"
+    }
+}
+
+// Draws the frames as an SVG image, where every bar is animated through its
+// heights and colors, frame_ms milliseconds per frame
+pub fn render_svg(frames: &[Frame], frame_ms: uint) -> String {
+    let len = frames[0].values.len();
+    let largest = frames[0].values.iter().map(|&x| max(x, 1)).max().unwrap_or(1);
+    let duration = frames.len() * frame_ms;
+
+    let mut out = String::new();
+    out.push_str(format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+                         len * BAR_WIDTH, SVG_HEIGHT).as_slice());
+    for i in range(0, len) {
+        let heights: Vec<uint> = frames.iter()
+                                       .map(|f| *f.values.get(i) * SVG_HEIGHT / largest)
+                                       .collect();
+        let ys: Vec<String> = heights.iter().map(|&h| (SVG_HEIGHT - h).to_str()).collect();
+        let hs: Vec<String> = heights.iter().map(|&h| h.to_str()).collect();
+        let colors: Vec<&str> = frames.iter().map(|f| bar_color(f, i)).collect();
+
+        out.push_str(format!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\">\n",
+                             i * BAR_WIDTH, ys.get(0), BAR_WIDTH - 1, hs.get(0),
+                             colors.get(0)).as_slice());
+        for &(attribute, ref values) in [("y", ys.as_slice().connect(";")),
+                                         ("height", hs.as_slice().connect(";")),
+                                         ("fill", colors.as_slice().connect(";"))].iter() {
+            out.push_str(format!("    <animate attributeName=\"{}\" values=\"{}\" dur=\"{}ms\" \
+                                  calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+                                 attribute, values, duration).as_slice());
+        }
+        out.push_str("  </rect>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(not(test))]
+fn show(sort: Sort, svg: bool, len: uint) {
+    let mut values: Vec<uint> = range(1, len + 1).collect();
+    task_rng().shuffle(values.as_mut_slice());
+
+    let initial = values.clone();
+    let events = sort_logged(sort, values.as_mut_slice());
+    let frames = frames(initial.as_slice(), events.as_slice());
+
+    if svg {
+        print!("{}", render_svg(frames.as_slice(), 100));
+    } else {
+        for frame in frames.iter() {
+            println!("{}", render_ascii(frame, len));
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let args = std::os::args();
+    if args.len() < 3 {
+        println!("Usage: {} (bubble|quick) (ascii|svg) [number of values]", args.get(0));
+        return;
+    }
+
+    let svg = match args.get(2).as_slice() {
+        "ascii" => false,
+        "svg" => true,
+        other => fail!("unknown output {}", other)
+    };
+    let len = if args.len() > 3 {
+        from_str(args.get(3).as_slice()).expect("the number of values must be a number")
+    } else {
+        10
+    };
+
+    match args.get(1).as_slice() {
+        "bubble" => show(Bubble, svg, len),
+        "quick" => show(Quick, svg, len),
+        other => fail!("unknown sort {}", other)
+    }
+}
+
+#[test]
+fn test_frames() {
+    let events = vec![Compare(0, 1), Swap(0, 1), Compare(1, 2), Write(2, 7)];
+    let frames = frames(&[2, 1, 3], events.as_slice());
+    let values: Vec<Vec<uint>> = frames.iter().map(|f| f.values.clone()).collect();
+    assert_eq!(values, vec![vec![2, 1, 3], vec![2, 1, 3], vec![1, 2, 3], vec![1, 2, 3],
+                            vec![1, 2, 7]]);
+    assert!(frames.get(0).event.is_none());
+    assert_eq!(frames.get(4).event, Some(Write(2, 7)));
+}
+
+#[test]
+fn test_render_ascii() {
+    let frames = frames(&[2, 1, 3], &[Compare(0, 1), Swap(0, 1)]);
+    assert_eq!(render_ascii(frames.get(0), 3), "  #\n# #\n###\nstart\n".to_string());
+    assert_eq!(render_ascii(frames.get(1), 3), "  #\n* #\n**#\ncompare 0 and 1\n".to_string());
+    assert_eq!(render_ascii(frames.get(2), 3), "  #\n @#\n@@#\nswap 0 and 1\n".to_string());
+}
+
+#[test]
+fn test_render_svg() {
+    let frames = frames(&[2, 1], &[Compare(0, 1), Swap(0, 1)]);
+    let svg = render_svg(frames.as_slice(), 100);
+    assert!(svg.as_slice().starts_with("<svg"));
+    // The first bar goes from 2 to 1, and is highlighted by both events
+    assert!(svg.as_slice().contains("values=\"0;0;100\""));
+    assert!(svg.as_slice().contains("values=\"200;200;100\""));
+    assert!(svg.as_slice().contains("values=\"steelblue;orange;red\""));
+    assert!(svg.as_slice().contains("dur=\"300ms\""));
+}
+
+#[test]
+fn test_logged_sorts() {
+    let mut values = vec![2u, 1];
+    assert_eq!(sort_logged(Bubble, values.as_mut_slice()), vec![Compare(0, 1), Swap(0, 1)]);
+    assert_eq!(values, vec![1, 2]);
+
+    // Short slices use insertion sort
+    let mut values = vec![3u, 1, 2];
+    assert_eq!(sort_logged(Quick, values.as_mut_slice()),
+               vec![Compare(1, 0), Swap(1, 0), Compare(2, 1), Swap(2, 1), Compare(1, 0)]);
+
+    // Replaying the log of a longer sort sorts the values too
+    let initial: Vec<uint> = range(0u, 100).map(|i| (i * 37) % 100).collect();
+    let mut values = initial.clone();
+    let events = sort_logged(Quick, values.as_mut_slice());
+    let frames = frames(initial.as_slice(), events.as_slice());
+    let sorted: Vec<uint> = range(0u, 100).collect();
+    assert_eq!(frames.last().unwrap().values, sorted);
+}